@@ -0,0 +1,101 @@
+// Ray throughput of the scene BVH on the `random_sphere` and `final_scene` presets,
+// for both splits and every width. Run with `cargo bench --bench bvh`.
+//
+// Every preset is traced with the same rays: a grid of primary rays through the
+// camera, then one diffuse bounce from each primary hit, which is far less coherent.
+// The bounces are also cast as occlusion queries, which stop at any hit.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ray_tracer_rs::bvh::{BvhOptions, BvhSplit, BvhWidth};
+use ray_tracer_rs::camera::Camera;
+use ray_tracer_rs::hittable::{HitRecord, Hittable};
+use ray_tracer_rs::hittable_list::HittableList;
+use ray_tracer_rs::interval::Interval;
+use ray_tracer_rs::ray::Ray;
+use ray_tracer_rs::rtweekend::{degrees2radians, seed_random, INF};
+use ray_tracer_rs::scenes;
+use ray_tracer_rs::vec3::Vec3;
+
+const GRID: usize = 256;
+const RUNS: usize = 5;
+
+fn main() {
+    println!(
+        "{:<14} {:<7} {:<6} {:>10} {:>16} {:>16} {:>16}",
+        "scene", "split", "width", "build", "primary", "bounce", "occluded"
+    );
+    for name in ["random_sphere", "final_scene"] {
+        for split in BvhSplit::ALL {
+            for width in BvhWidth::ALL {
+                seed_random(0);
+                let scene = scenes::find(name).expect("preset scene");
+                let start = Instant::now();
+                let (world, cam) = scene(BvhOptions { split, width }).expect("preset scene loads");
+                let build = start.elapsed();
+
+                let primary = primary_rays(&cam);
+                let bounce = bounce_rays(&world, &primary);
+                let closest = |r: &Ray| {
+                    let mut rec = HitRecord::new();
+                    world.hit(r, Interval::from(0.001, INF), &mut rec)
+                };
+                let any = |r: &Ray| world.occluded(r, Interval::from(0.001, INF));
+                println!(
+                    "{:<14} {:<7} {:<6} {:>8.1}ms {:>10.2} Mray/s {:>10.2} Mray/s {:>10.2} Mray/s",
+                    name,
+                    split.name(),
+                    width.name(),
+                    build.as_secs_f64() * 1e3,
+                    throughput(&primary, closest),
+                    throughput(&bounce, closest),
+                    throughput(&bounce, any),
+                );
+            }
+        }
+    }
+}
+
+// Pinhole rays through the centers of a square grid over the view of `cam`.
+fn primary_rays(cam: &Camera) -> Vec<Ray> {
+    let w = (cam.lookfrom - cam.lookat).unit_vector();
+    let u = cam.vup.cross(w).unit_vector();
+    let v = w.cross(u);
+    let half = (degrees2radians(cam.vfov) / 2.0).tan();
+    let mut rays = Vec::with_capacity(GRID * GRID);
+    for j in 0..GRID {
+        for i in 0..GRID {
+            let x = (2.0 * (i as f64 + 0.5) / GRID as f64 - 1.0) * half * cam.aspect_ratio;
+            let y = (1.0 - 2.0 * (j as f64 + 0.5) / GRID as f64) * half;
+            rays.push(Ray::from(cam.lookfrom, u * x + v * y - w, 0.5));
+        }
+    }
+    rays
+}
+
+fn bounce_rays(world: &HittableList, primary: &[Ray]) -> Vec<Ray> {
+    seed_random(1);
+    primary
+        .iter()
+        .filter_map(|r| {
+            let mut rec = HitRecord::new();
+            world
+                .hit(r, Interval::from(0.001, INF), &mut rec)
+                .then(|| Ray::from(rec.p, rec.normal + Vec3::random_unit_vector(), r.time()))
+        })
+        .collect()
+}
+
+// Best of a few runs, in millions of queries per second.
+fn throughput(rays: &[Ray], query: impl Fn(&Ray) -> bool) -> f64 {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        for r in rays {
+            black_box(query(r));
+        }
+        best = best.min(start.elapsed());
+    }
+    rays.len() as f64 / best.as_secs_f64() / 1e6
+}
@@ -1,5 +1,5 @@
 use crate::{
-    interval::Interval,
+    interval::{self, Interval},
     ray::Ray,
     vec3::{Point3, Vec3},
 };
@@ -12,6 +12,13 @@ pub struct Aabb {
     z: Interval,
 }
 
+// Holds nothing, so merging it with another box gives that box.
+pub const EMPTY: Aabb = Aabb {
+    x: interval::EMPTY,
+    y: interval::EMPTY,
+    z: interval::EMPTY,
+};
+
 impl Aabb {
     pub fn new() -> Self {
         Self {
@@ -45,9 +52,9 @@ impl Aabb {
     }
     pub fn from_points(a: Point3, b: Point3) -> Self {
         Self {
-            x: Interval::from((a[0] as f64).min(b[0]), (a[0] as f64).max(b[0])),
-            y: Interval::from((a[1] as f64).min(b[1]), (a[1] as f64).max(b[1])),
-            z: Interval::from((a[2] as f64).min(b[2]), (a[2] as f64).max(b[2])),
+            x: Interval::from(a.x().min(b.x()), a.x().max(b.x())),
+            y: Interval::from(a.y().min(b.y()), a.y().max(b.y())),
+            z: Interval::from(a.z().min(b.z()), a.z().max(b.z())),
         }
     }
     pub fn from_bbox(box0: Aabb, box1: Aabb) -> Self {
@@ -59,8 +66,8 @@ impl Aabb {
     }
     pub fn axis(&self, n: i32) -> Interval {
         match n {
-            1 => return self.y,
-            2 => return self.z,
+            1 => self.y,
+            2 => self.z,
             _ => self.x,
         }
     }
@@ -73,6 +80,18 @@ impl Aabb {
     pub fn z(self) -> Interval {
         self.z
     }
+    pub fn centroid(&self) -> Point3 {
+        Point3::from(
+            (self.x.min() + self.x.max()) / 2.0,
+            (self.y.min() + self.y.max()) / 2.0,
+            (self.z.min() + self.z.max()) / 2.0,
+        )
+    }
+    // Empty boxes have no area rather than a negative one.
+    pub fn surface_area(&self) -> f64 {
+        let [x, y, z] = [self.x, self.y, self.z].map(|i| i.size().max(0.0));
+        2.0 * (x * y + y * z + z * x)
+    }
     pub fn hit(&self, r: &Ray, ray_t: &mut Interval) -> bool {
         for a in 0..3 {
             let inv_d = 1.0 / r.direction()[a];
@@ -82,9 +101,7 @@ impl Aabb {
             let mut t1 = (self.axis(a).max() - orig) * inv_d;
 
             if inv_d < 0.0 {
-                let save = t0;
-                t0 = t1;
-                t1 = save;
+                std::mem::swap(&mut t0, &mut t1);
             }
             if t0 > ray_t.min() {
                 ray_t.modify_min(t0);
@@ -100,9 +117,170 @@ impl Aabb {
     }
 }
 
+// A ray set up once for slab tests against many boxes.
+#[derive(Debug, Clone, Copy)]
+pub struct SlabRay {
+    origin: [f64; 3],
+    inv_dir: [f64; 3],
+    negative: [bool; 3], // The ray enters the box through its max side
+    simd: bool,
+}
+
+impl SlabRay {
+    pub fn from(r: &Ray) -> Self {
+        let inv_dir = [0, 1, 2].map(|a| 1.0 / r.direction()[a]);
+        Self {
+            origin: [0, 1, 2].map(|a| r.origin()[a]),
+            inv_dir,
+            negative: inv_dir.map(|inv_d| inv_d < 0.0),
+            simd: simd::available(),
+        }
+    }
+}
+
+// `N` boxes stored axis by axis, so that a ray is tested against all of them at
+// once. Lanes without a box hold an empty one, which no ray hits.
+#[derive(Debug, Clone, Copy)]
+pub struct AabbPack<const N: usize> {
+    min: [[f64; N]; 3],
+    max: [[f64; N]; 3],
+}
+
+impl<const N: usize> AabbPack<N> {
+    pub fn from(boxes: &[Aabb]) -> Self {
+        assert!(boxes.len() <= N, "a pack holds at most {} boxes", N);
+        let mut pack = Self {
+            min: [[interval::EMPTY.min(); N]; 3],
+            max: [[interval::EMPTY.max(); N]; 3],
+        };
+        for (i, bbox) in boxes.iter().enumerate() {
+            pack.set(i, *bbox);
+        }
+        pack
+    }
+    pub fn get(&self, i: usize) -> Aabb {
+        let [x, y, z] = [0, 1, 2].map(|a| Interval::from(self.min[a][i], self.max[a][i]));
+        Aabb { x, y, z }
+    }
+    pub fn set(&mut self, i: usize, bbox: Aabb) {
+        for a in 0..3 {
+            self.min[a][i] = bbox.axis(a as i32).min();
+            self.max[a][i] = bbox.axis(a as i32).max();
+        }
+    }
+    // Box around every box of the pack.
+    pub fn bounds(&self) -> Aabb {
+        (0..N).map(|i| self.get(i)).fold(EMPTY, Aabb::from_bbox)
+    }
+
+    // The same test as `Aabb::hit` for every box: bit `i` of the mask is set when
+    // the ray is in box `i` somewhere within `ray_t`, from the distance at `i`.
+    pub fn hit(&self, r: &SlabRay, ray_t: Interval) -> (u32, [f64; N]) {
+        let mut entry = [ray_t.min(); N];
+        let mut exit = [ray_t.max(); N];
+        if r.simd && N.is_multiple_of(4) {
+            // SAFETY: `simd` is only set when the CPU has the instructions used.
+            return unsafe { simd::hit(self, r, &mut entry, &mut exit) };
+        }
+        for a in 0..3 {
+            let (near, far) = match r.negative[a] {
+                true => (&self.max[a], &self.min[a]),
+                false => (&self.min[a], &self.max[a]),
+            };
+            for i in 0..N {
+                let t0 = (near[i] - r.origin[a]) * r.inv_dir[a];
+                let t1 = (far[i] - r.origin[a]) * r.inv_dir[a];
+                if t0 > entry[i] {
+                    entry[i] = t0;
+                }
+                if t1 < exit[i] {
+                    exit[i] = t1;
+                }
+            }
+        }
+        let mask = (0..N).fold(0, |mask, i| mask | ((exit[i] > entry[i]) as u32) << i);
+        (mask, entry)
+    }
+}
+
+// Four lanes at a time with AVX, on x86_64 processors that have it.
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use std::arch::x86_64::*;
+
+    use super::{AabbPack, SlabRay};
+
+    pub fn available() -> bool {
+        is_x86_feature_detected!("avx")
+    }
+
+    // Same operations as the scalar test, in the same order, so that both give
+    // the same distances. With a NaN operand the AVX min and max return their
+    // second one, which keeps the running bound like the scalar comparisons do.
+    #[target_feature(enable = "avx")]
+    pub unsafe fn hit<const N: usize>(
+        pack: &AabbPack<N>,
+        r: &SlabRay,
+        entry: &mut [f64; N],
+        exit: &mut [f64; N],
+    ) -> (u32, [f64; N]) {
+        let mut mask = 0;
+        for i in (0..N).step_by(4) {
+            let mut t_min = _mm256_loadu_pd(entry[i..].as_ptr());
+            let mut t_max = _mm256_loadu_pd(exit[i..].as_ptr());
+            for a in 0..3 {
+                let (near, far) = match r.negative[a] {
+                    true => (&pack.max[a], &pack.min[a]),
+                    false => (&pack.min[a], &pack.max[a]),
+                };
+                let origin = _mm256_set1_pd(r.origin[a]);
+                let inv_dir = _mm256_set1_pd(r.inv_dir[a]);
+                let t0 = _mm256_mul_pd(
+                    _mm256_sub_pd(_mm256_loadu_pd(near[i..].as_ptr()), origin),
+                    inv_dir,
+                );
+                let t1 = _mm256_mul_pd(
+                    _mm256_sub_pd(_mm256_loadu_pd(far[i..].as_ptr()), origin),
+                    inv_dir,
+                );
+                t_min = _mm256_max_pd(t0, t_min);
+                t_max = _mm256_min_pd(t1, t_max);
+            }
+            _mm256_storeu_pd(entry[i..].as_mut_ptr(), t_min);
+            let hits = _mm256_movemask_pd(_mm256_cmp_pd::<_CMP_GT_OQ>(t_max, t_min));
+            mask |= (hits as u32) << i;
+        }
+        (mask, *entry)
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+mod simd {
+    use super::{AabbPack, SlabRay};
+
+    pub fn available() -> bool {
+        false
+    }
+
+    pub unsafe fn hit<const N: usize>(
+        _: &AabbPack<N>,
+        _: &SlabRay,
+        _: &mut [f64; N],
+        _: &mut [f64; N],
+    ) -> (u32, [f64; N]) {
+        unreachable!("no SIMD slab test on this architecture")
+    }
+}
+
 impl ops::Add<Vec3> for Aabb {
     type Output = Aabb;
     fn add(self, rhs: Vec3) -> Self::Output {
         Aabb::from_bbox_vec(self, rhs)
     }
 }
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
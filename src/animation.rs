@@ -0,0 +1,171 @@
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use crate::camera::{Camera, CameraKeyframe};
+use crate::error::{Error, Result};
+use crate::rtweekend::mix_seed;
+use crate::vec3::Point3;
+
+// Camera settings at a moment of an animation, in seconds. Settings in between keys
+// are interpolated linearly, and hold before the first and after the last.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKey {
+    pub time: f64,
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vfov: f64,
+    pub focus_dist: f64,
+    pub defocus_angle: f64,
+}
+
+impl CameraKey {
+    // The settings of `cam`, at `time`.
+    pub fn from(time: f64, cam: &Camera) -> Self {
+        Self {
+            time,
+            lookfrom: cam.lookfrom,
+            lookat: cam.lookat,
+            vfov: cam.vfov,
+            focus_dist: cam.focus_dist,
+            defocus_angle: cam.defocus_angle,
+        }
+    }
+    fn lerp(&self, next: &CameraKey, time: f64) -> CameraKey {
+        let s = (time - self.time) / (next.time - self.time);
+        let mix = |a: f64, b: f64| a * (1.0 - s) + b * s;
+        CameraKey {
+            time,
+            lookfrom: self.lookfrom * (1.0 - s) + next.lookfrom * s,
+            lookat: self.lookat * (1.0 - s) + next.lookat * s,
+            vfov: mix(self.vfov, next.vfov),
+            focus_dist: mix(self.focus_dist, next.focus_dist),
+            defocus_angle: mix(self.defocus_angle, next.defocus_angle),
+        }
+    }
+}
+
+// A sequence of frames, frame `n` showing the scene at `n / fps` seconds. Ray times
+// count seconds in an animation, so moving objects and animated instances play out
+// across the frames. The shutter times and motion keyframes of the scene camera
+// become fractions of a frame interval, starting at the time of each frame.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub first_frame: u32,
+    pub last_frame: u32,
+    pub fps: f64,
+    pub camera: Vec<CameraKey>, // In time order, empty to keep the scene camera
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Self {
+            first_frame: 0,
+            last_frame: 0,
+            fps: 24.0,
+            camera: Vec::new(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(Error::InvalidAnimation(reason));
+        if !(self.fps > 0.0 && self.fps.is_finite()) {
+            return invalid("fps must be positive".to_string());
+        }
+        if self.first_frame > self.last_frame {
+            return invalid(format!(
+                "frame range {}-{} ends before it starts",
+                self.first_frame, self.last_frame
+            ));
+        }
+        if self.camera.iter().any(|key| !key.time.is_finite()) {
+            return invalid("camera key times must be finite".to_string());
+        }
+        if self
+            .camera
+            .windows(2)
+            .any(|pair| pair[0].time >= pair[1].time)
+        {
+            return invalid("camera keys must be in time order".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn frames(&self) -> RangeInclusive<u32> {
+        self.first_frame..=self.last_frame
+    }
+    pub fn time(&self, frame: u32) -> f64 {
+        frame as f64 / self.fps
+    }
+
+    pub fn camera_at(&self, time: f64) -> Option<CameraKey> {
+        let k = &self.camera;
+        match k.partition_point(|key| key.time <= time) {
+            _ if k.is_empty() => None,
+            0 => Some(k[0]),
+            n if n == k.len() => Some(k[n - 1]),
+            n => Some(k[n - 1].lerp(&k[n], time)),
+        }
+    }
+
+    // The camera of `frame`, set up from `base`, the camera of the scene. Each frame
+    // gets its own sample seed, so the noise does not stick to the screen.
+    pub fn camera(&self, base: &Camera, frame: u32) -> Camera {
+        let mut cam = base.clone();
+        let time = self.time(frame);
+        let at = |fraction: f64| time + fraction / self.fps;
+        cam.shutter_open = at(base.shutter_open);
+        cam.shutter_close = at(base.shutter_close);
+        cam.seed = mix_seed(base.seed, frame as u64);
+        for key in cam.motion.iter_mut() {
+            key.time = at(key.time);
+        }
+
+        if let Some(key) = self.camera_at(time) {
+            cam.lookfrom = key.lookfrom;
+            cam.lookat = key.lookat;
+            cam.vfov = key.vfov;
+            cam.focus_dist = key.focus_dist;
+            cam.defocus_angle = key.defocus_angle;
+            // Keep following the path while the shutter is open.
+            cam.motion = [cam.shutter_open, cam.shutter_close]
+                .iter()
+                .filter_map(|&time| {
+                    self.camera_at(time).map(|key| CameraKeyframe {
+                        time,
+                        lookfrom: key.lookfrom,
+                        lookat: key.lookat,
+                    })
+                })
+                .collect();
+        }
+        cam
+    }
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// File name of `frame`: the last run of `#` in `pattern` is replaced by the frame
+// number padded to as many digits, otherwise four digits go before the extension.
+pub fn frame_path(pattern: &str, frame: u32) -> PathBuf {
+    if let Some(end) = pattern.rfind('#') {
+        let start = pattern[..end].trim_end_matches('#').len();
+        let width = end + 1 - start;
+        return format!(
+            "{}{:0width$}{}",
+            &pattern[..start],
+            frame,
+            &pattern[end + 1..]
+        )
+        .into();
+    }
+    let path = Path::new(pattern);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("frame");
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => path.with_file_name(format!("{}_{:04}.{}", stem, frame, extension)),
+        None => path.with_file_name(format!("{}_{:04}", stem, frame)),
+    }
+}
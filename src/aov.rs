@@ -0,0 +1,67 @@
+use crate::{color::Color, hittable::HitRecord, ray::Ray};
+
+// Auxiliary passes that can be recorded from the first hit of every camera ray.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    Depth,
+    Normal,
+    Position,
+    Albedo,
+    ObjectId,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 5] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Position,
+        Aov::Albedo,
+        Aov::ObjectId,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|aov| aov.name() == name)
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Position => "position",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+        }
+    }
+    // Channels kept when the pass is written as an OpenEXR layer.
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Albedo => &["R", "G", "B"],
+            Aov::ObjectId => &["id"],
+        }
+    }
+    // Whether the pass holds data rather than a color, which 8-bit files cannot
+    // store as it is.
+    pub fn is_data(self) -> bool {
+        self != Aov::Albedo
+    }
+    // Whether samples of the pass can be averaged over a pixel, an id cannot.
+    pub fn is_filtered(self) -> bool {
+        self != Aov::ObjectId
+    }
+
+    // Value of the pass for a camera ray, `rec` being its first hit if any.
+    // Rays escaping the scene leave every pass at zero.
+    pub fn sample(self, r: &Ray, rec: Option<&HitRecord>) -> Color {
+        let Some(rec) = rec else {
+            return Color::zeros();
+        };
+        match self {
+            Aov::Depth => Color::ones() * (rec.t * r.direction().length()),
+            Aov::Normal => rec.normal,
+            Aov::Position => rec.p,
+            Aov::Albedo => rec.mat.albedo(rec),
+            Aov::ObjectId => Color::ones() * rec.object_id as f64,
+        }
+    }
+}
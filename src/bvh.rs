@@ -1,132 +1,765 @@
 use std::cmp::Ordering;
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::aabb::Aabb;
-use crate::hittable::{HitRecord, Hittable};
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::aabb::{self, Aabb, AabbPack, SlabRay};
+use crate::hittable::{refit_object, HitRecord, Hittable};
 use crate::hittable_list::HittableList;
 use crate::interval::Interval;
 use crate::ray::Ray;
-use crate::rtweekend::random_int;
+use crate::rtweekend::seeded_rng;
+use crate::vec3::Point3;
+
+// Objects are paired with their index + 1 in the source list, which leaves report
+// as the object id of their hits.
+type Entry = (u32, Arc<dyn Hittable>);
+
+// How a list of objects is divided between the two children of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BvhSplit {
+    // Sort along a random axis and cut at the median, down to one or two objects.
+    // The axes come from a stream of their own, started from the scene seed, so that
+    // building a tree leaves the random draws of the scene around it unchanged.
+    #[default]
+    Median,
+    // Choose the axis and position with the lowest surface area heuristic cost,
+    // over binned centroids, and keep up to `SAH_LEAF_SIZE` objects in a leaf.
+    Sah,
+}
+
+impl BvhSplit {
+    pub const ALL: [BvhSplit; 2] = [BvhSplit::Median, BvhSplit::Sah];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|split| split.name() == name)
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            BvhSplit::Median => "median",
+            BvhSplit::Sah => "sah",
+        }
+    }
+}
+
+// How many children the nodes of a tree have. Wider trees are collapsed from the
+// binary one, and test the boxes of all the children of a node at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BvhWidth {
+    #[default]
+    Two,
+    Four,
+    Eight,
+}
+
+impl BvhWidth {
+    pub const ALL: [BvhWidth; 3] = [BvhWidth::Two, BvhWidth::Four, BvhWidth::Eight];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|width| width.name() == name)
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            BvhWidth::Two => "2",
+            BvhWidth::Four => "4",
+            BvhWidth::Eight => "8",
+        }
+    }
+}
+
+// Split and width of the trees a scene builds, handed down to whatever builds them
+// so that the built-in scenes can be compared under any of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BvhOptions {
+    pub split: BvhSplit,
+    pub width: BvhWidth,
+}
+
+const SAH_BINS: usize = 16;
+const SAH_LEAF_SIZE: usize = 4;
+// Cost of visiting a node relative to intersecting an object.
+const SAH_TRAVERSAL_COST: f64 = 1.0;
+const SAH_MAX_DEPTH: usize = 32;
+// Deep enough for the median tree of any list, and for the SAH tree, whose binned
+// splits stop at `SAH_MAX_DEPTH`. Wide nodes push up to one entry per child.
+const STACK_SIZE: usize = 64;
+// Refit trees are rebuilt once their cost grows past this much of the cost they had
+// when built.
+const REBUILD_COST_RATIO: f64 = 1.5;
 
 pub struct BvhNode {
-    left: Option<Rc<dyn Hittable>>,
-    right: Option<Rc<dyn Hittable>>,
     bbox: Aabb,
+    tree: Tree,
+    objects: Vec<Entry>,
+    split: BvhSplit,
+    width: BvhWidth,
+    // Cost of the tree when it was last built, see `refit`.
+    built_cost: Option<f64>,
 }
-//
+
+// The nodes of the tree in an array, the root first.
+enum Tree {
+    Binary(Vec<Node>),
+    Four(Vec<WideNode<4>>),
+    Eight(Vec<WideNode<8>>),
+}
+
+// Leaves hold `count` objects starting at `first`, interior nodes have a count of 0
+// and their two children stored next to each other at `first`.
+#[derive(Clone, Copy)]
+struct Node {
+    bbox: Aabb,
+    first: u32,
+    count: u32,
+}
+
+// Up to `N` children, each either `count` objects at `first` or, with a count of 0,
+// the node at `first`. Unused lanes have an empty box.
+#[derive(Clone, Copy)]
+struct WideNode<const N: usize> {
+    boxes: AabbPack<N>,
+    first: [u32; N],
+    count: [u32; N],
+}
+
+// The binary tree while it is built.
+struct Builder {
+    nodes: Vec<Node>,
+    objects: Vec<Entry>,
+}
+
+// An object with the box and centroid the SAH builder bins it by.
+struct Prim {
+    entry: Entry,
+    bbox: Aabb,
+    centroid: Point3,
+}
+
 impl BvhNode {
     pub fn new() -> Self {
         Self {
-            left: None,
-            right: None,
-            bbox: Aabb::new(),
+            bbox: aabb::EMPTY,
+            tree: Tree::Binary(Vec::new()),
+            objects: Vec::new(),
+            split: BvhSplit::default(),
+            width: BvhWidth::default(),
+            built_cost: None,
         }
     }
     pub fn from_list(list: &mut HittableList) -> Self {
-        let len = list.objects.len();
-        BvhNode::from(&mut list.objects, 0, len)
+        BvhNode::from_list_with(list, BvhOptions::default())
+    }
+    pub fn from_list_with(list: &mut HittableList, options: BvhOptions) -> Self {
+        BvhNode::build(list, options.split, options.width)
+    }
+    pub fn build(list: &mut HittableList, split: BvhSplit, width: BvhWidth) -> Self {
+        let objects = (1..).zip(list.objects.iter().cloned()).collect();
+        BvhNode::from_entries(objects, split, width)
     }
-    fn from(src_objects: &mut Vec<Rc<dyn Hittable>>, start: usize, end: usize) -> Self {
-        let mut objects = src_objects.clone();
 
-        let axis = random_int(0, 2);
+    fn from_entries(mut objects: Vec<Entry>, split: BvhSplit, width: BvhWidth) -> Self {
+        if objects.is_empty() {
+            return BvhNode {
+                split,
+                width,
+                ..BvhNode::new()
+            };
+        }
+        let mut bvh = Builder {
+            nodes: Vec::with_capacity(2 * objects.len()),
+            objects: Vec::with_capacity(objects.len()),
+        };
+        bvh.nodes.push(Node::EMPTY);
+        match split {
+            BvhSplit::Median => {
+                let mut rng = seeded_rng();
+                bvh.median(0, &mut objects, &mut rng);
+            }
+            BvhSplit::Sah => {
+                let mut prims: Vec<Prim> = objects
+                    .into_iter()
+                    .map(|entry| {
+                        let bbox = entry.1.bounding_box();
+                        Prim {
+                            entry,
+                            bbox,
+                            centroid: bbox.centroid(),
+                        }
+                    })
+                    .collect();
+                bvh.sah(0, &mut prims, 0);
+            }
+        }
+
+        let Builder { nodes, objects } = bvh;
+        let mut node = BvhNode {
+            bbox: nodes[0].bbox,
+            tree: match width {
+                BvhWidth::Two => Tree::Binary(nodes),
+                BvhWidth::Four => Tree::Four(widen(&nodes)),
+                BvhWidth::Eight => Tree::Eight(widen(&nodes)),
+            },
+            objects,
+            split,
+            width,
+            built_cost: None,
+        };
+        node.built_cost = Some(node.sah_cost());
+        node
+    }
+
+    // Surface area heuristic cost of the tree as its boxes stand, relative to the root
+    // box: visiting a node costs `SAH_TRAVERSAL_COST`, and testing an object 1.
+    fn sah_cost(&self) -> f64 {
+        let area = self.bbox.surface_area();
+        if area <= 0.0 {
+            return 0.0;
+        }
+        let total: f64 = match &self.tree {
+            Tree::Binary(nodes) => nodes
+                .iter()
+                .map(|node| match node.count {
+                    0 => SAH_TRAVERSAL_COST * node.bbox.surface_area(),
+                    count => node.bbox.surface_area() * count as f64,
+                })
+                .sum(),
+            Tree::Four(nodes) => wide_cost(nodes),
+            Tree::Eight(nodes) => wide_cost(nodes),
+        };
+        total / area
+    }
+
+    fn rebuild(&mut self) {
+        let mut objects = std::mem::take(&mut self.objects);
+        objects.sort_unstable_by_key(|(id, _)| *id);
+        *self = BvhNode::from_entries(objects, self.split, self.width);
+    }
+}
+
+impl Builder {
+    fn leaf(&mut self, node: usize, objects: impl IntoIterator<Item = Entry>) {
+        let first = self.objects.len();
+        self.objects.extend(objects);
+        let bbox = self.objects[first..]
+            .iter()
+            .map(|(_, object)| object.bounding_box())
+            .fold(aabb::EMPTY, Aabb::from_bbox);
+        self.nodes[node] = Node {
+            bbox,
+            first: first as u32,
+            count: (self.objects.len() - first) as u32,
+        };
+    }
+    // Make `node` interior, returning the index of its first child, whose boxes are
+    // merged by `close` once built.
+    fn split(&mut self, node: usize) -> usize {
+        let left = self.nodes.len();
+        self.nodes[node].first = left as u32;
+        self.nodes.extend([Node::EMPTY; 2]);
+        left
+    }
+    fn close(&mut self, node: usize, left: usize) {
+        self.nodes[node].bbox = Aabb::from_bbox(self.nodes[left].bbox, self.nodes[left + 1].bbox);
+    }
+
+    fn median(&mut self, node: usize, objects: &mut [Entry], rng: &mut ChaCha8Rng) {
+        let axis = rng.gen_range(0..3);
 
-        let comparator: fn(&Rc<dyn Hittable>, &Rc<dyn Hittable>) -> Ordering = match axis {
-            0 => BvhNode::box_x_compare,
-            1 => BvhNode::box_y_compare,
-            _ => BvhNode::box_z_compare,
+        let comparator: fn(&Entry, &Entry) -> Ordering = match axis {
+            0 => Builder::box_x_compare,
+            1 => Builder::box_y_compare,
+            _ => Builder::box_z_compare,
         };
-        let object_span = end - start;
+        let object_span = objects.len();
 
         if object_span == 1 {
-            return BvhNode {
-                left: Some(objects[start].clone()),
-                right: Some(objects[start].clone()),
-                bbox: Aabb::from_bbox(
-                    objects[start].clone().bounding_box(),
-                    objects[start].clone().bounding_box(),
-                ),
-            };
+            return self.leaf(node, [objects[0].clone()]);
         } else if object_span == 2 {
-            if comparator(&objects[start], &objects[start + 1]) == Ordering::Equal {
-                return BvhNode {
-                    left: Some(objects[start].clone()),
-                    right: Some(objects[start + 1].clone()),
-                    bbox: Aabb::from_bbox(
-                        objects[start].clone().bounding_box(),
-                        objects[start + 1].clone().bounding_box(),
-                    ),
-                };
+            if comparator(&objects[0], &objects[1]) == Ordering::Equal {
+                return self.leaf(node, [objects[0].clone(), objects[1].clone()]);
             } else {
-                return BvhNode {
-                    left: Some(objects[start + 1].clone()),
-                    right: Some(objects[start].clone()),
-                    bbox: Aabb::from_bbox(
-                        objects[start + 1].clone().bounding_box(),
-                        objects[start].clone().bounding_box(),
-                    ),
-                };
+                return self.leaf(node, [objects[1].clone(), objects[0].clone()]);
             }
         }
-        objects[start..end].sort_by(|a, b| comparator(a, b));
+        objects.sort_by(comparator);
 
-        let mid = start + object_span / 2;
-        let left_node = BvhNode::from(&mut objects, start, mid);
-        let right_node = BvhNode::from(&mut objects, mid, end);
+        let (left, right) = objects.split_at_mut(object_span / 2);
+        let first = self.split(node);
+        self.median(first, left, rng);
+        self.median(first + 1, right, rng);
+        self.close(node, first);
+    }
+
+    fn sah(&mut self, node: usize, prims: &mut [Prim], depth: usize) {
+        let count = prims.len();
+        if count <= 1 {
+            return self.leaf(node, prims.iter().map(|p| p.entry.clone()));
+        }
 
-        let left_bbox = left_node.bbox.clone();
-        let right_bbox = right_node.bbox.clone();
+        let bounds = prims
+            .iter()
+            .map(|p| p.bbox)
+            .reduce(Aabb::from_bbox)
+            .unwrap();
+        let centroids = prims
+            .iter()
+            .map(|p| Aabb::from_points(p.centroid, p.centroid))
+            .reduce(Aabb::from_bbox)
+            .unwrap();
+        let area = bounds.surface_area();
+
+        // The cheapest (cost, axis, bin) split, objects in bins below `bin` going left.
+        // Past `SAH_MAX_DEPTH` groups are only halved, which bounds the depth of the
+        // tree for the traversal stack.
+        let mut best: Option<(f64, i32, usize)> = None;
+        for axis in (0..3).filter(|_| depth < SAH_MAX_DEPTH) {
+            let extent = centroids.axis(axis);
+            if extent.size() <= 0.0 {
+                continue;
+            }
+            let mut bins: [(usize, Option<Aabb>); SAH_BINS] = [(0, None); SAH_BINS];
+            for p in prims.iter() {
+                let bin = &mut bins[bin_index(p.centroid[axis as usize], extent)];
+                bin.0 += 1;
+                bin.1 = Some(bin.1.map_or(p.bbox, |b| Aabb::from_bbox(b, p.bbox)));
+            }
 
-        BvhNode {
-            left: Some(Rc::new(left_node)),
-            right: Some(Rc::new(right_node)),
-            bbox: Aabb::from_bbox(left_bbox, right_bbox),
+            // Sweep from the right to know the area and count beyond every cut.
+            let mut right = [(0, 0.0); SAH_BINS];
+            let (mut n, mut bbox): (usize, Option<Aabb>) = (0, None);
+            for i in (1..SAH_BINS).rev() {
+                n += bins[i].0;
+                bbox = merge(bbox, bins[i].1);
+                right[i] = (n, bbox.map_or(0.0, |b| b.surface_area()));
+            }
+            let (mut n, mut bbox): (usize, Option<Aabb>) = (0, None);
+            for (i, bin) in bins.iter().enumerate().take(SAH_BINS - 1) {
+                n += bin.0;
+                bbox = merge(bbox, bin.1);
+                let (right_n, right_area) = right[i + 1];
+                if n == 0 || right_n == 0 {
+                    continue;
+                }
+                let left_area = bbox.map_or(0.0, |b| b.surface_area());
+                let cost = SAH_TRAVERSAL_COST
+                    + (left_area * n as f64 + right_area * right_n as f64) / area;
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, i + 1));
+                }
+            }
         }
+
+        let split_at = match best {
+            // Splitting would cost more than testing every object of a small leaf.
+            Some((cost, _, _)) if count <= SAH_LEAF_SIZE && cost >= count as f64 => {
+                return self.leaf(node, prims.iter().map(|p| p.entry.clone()));
+            }
+            Some((_, axis, bin)) => {
+                let extent = centroids.axis(axis);
+                partition(prims, |p| {
+                    bin_index(p.centroid[axis as usize], extent) < bin
+                })
+            }
+            // Every centroid is the same point: leaves take what they can, larger
+            // groups are halved as they come.
+            None if count <= SAH_LEAF_SIZE => {
+                return self.leaf(node, prims.iter().map(|p| p.entry.clone()));
+            }
+            None => count / 2,
+        };
+        let (left, right) = prims.split_at_mut(split_at);
+        let first = self.split(node);
+        self.sah(first, left, depth + 1);
+        self.sah(first + 1, right, depth + 1);
+        self.close(node, first);
     }
-    fn box_compare(a: &Rc<dyn Hittable>, b: &Rc<dyn Hittable>, axis_index: i32) -> Ordering {
-        a.bounding_box()
+
+    fn box_compare(a: &Entry, b: &Entry, axis_index: i32) -> Ordering {
+        a.1.bounding_box()
             .axis(axis_index)
             .min()
-            .partial_cmp(&b.bounding_box().axis(axis_index).min())
+            .partial_cmp(&b.1.bounding_box().axis(axis_index).min())
             .unwrap_or(Ordering::Equal)
     }
-    fn box_x_compare(a: &Rc<dyn Hittable>, b: &Rc<dyn Hittable>) -> Ordering {
-        BvhNode::box_compare(a, b, 0)
+    fn box_x_compare(a: &Entry, b: &Entry) -> Ordering {
+        Builder::box_compare(a, b, 0)
     }
-    fn box_y_compare(a: &Rc<dyn Hittable>, b: &Rc<dyn Hittable>) -> Ordering {
-        BvhNode::box_compare(a, b, 1)
+    fn box_y_compare(a: &Entry, b: &Entry) -> Ordering {
+        Builder::box_compare(a, b, 1)
     }
-    fn box_z_compare(a: &Rc<dyn Hittable>, b: &Rc<dyn Hittable>) -> Ordering {
-        BvhNode::box_compare(a, b, 2)
+    fn box_z_compare(a: &Entry, b: &Entry) -> Ordering {
+        Builder::box_compare(a, b, 2)
     }
 }
 
-impl Hittable for BvhNode {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
-        if !self.bbox.hit(r, &mut ray_t.clone()) {
+impl Node {
+    const EMPTY: Node = Node {
+        bbox: aabb::EMPTY,
+        first: 0,
+        count: 0,
+    };
+}
+
+// Collapse a binary tree into one of `N` wide nodes: each node opens up the interior
+// child with the largest box until it has `N` children.
+fn widen<const N: usize>(binary: &[Node]) -> Vec<WideNode<N>> {
+    let mut nodes = Vec::with_capacity(binary.len() / (N - 1) + 1);
+    collapse(binary, 0, &mut nodes);
+    nodes
+}
+
+// Add the wide node for binary node `node`, returning its index.
+fn collapse<const N: usize>(binary: &[Node], node: usize, nodes: &mut Vec<WideNode<N>>) -> u32 {
+    let mut lanes = vec![node];
+    while lanes.len() < N {
+        let Some(i) = (0..lanes.len())
+            .filter(|&i| binary[lanes[i]].count == 0)
+            .max_by(|&a, &b| {
+                let area = |i: usize| binary[lanes[i]].bbox.surface_area();
+                area(a).total_cmp(&area(b))
+            })
+        else {
+            break;
+        };
+        let first = binary[lanes[i]].first as usize;
+        lanes.splice(i..=i, [first, first + 1]);
+    }
+
+    let index = nodes.len();
+    let boxes: Vec<Aabb> = lanes.iter().map(|&lane| binary[lane].bbox).collect();
+    nodes.push(WideNode {
+        boxes: AabbPack::from(&boxes),
+        first: [0; N],
+        count: [0; N],
+    });
+    for (i, &lane) in lanes.iter().enumerate() {
+        let (first, count) = match binary[lane].count {
+            0 => (collapse(binary, lane, nodes), 0),
+            count => (binary[lane].first, count),
+        };
+        nodes[index].first[i] = first;
+        nodes[index].count[i] = count;
+    }
+    index as u32
+}
+
+fn wide_cost<const N: usize>(nodes: &[WideNode<N>]) -> f64 {
+    nodes
+        .iter()
+        .map(|node| {
+            let leaves: f64 = (0..N)
+                .filter(|&i| node.count[i] > 0)
+                .map(|i| node.boxes.get(i).surface_area() * node.count[i] as f64)
+                .sum();
+            SAH_TRAVERSAL_COST * node.boxes.bounds().surface_area() + leaves
+        })
+        .sum()
+}
+
+// Children come after their parent, so going backwards refits them first. The root is
+// no one's child, so a lane of count 0 pointing at it is unused.
+fn refit_wide<const N: usize>(nodes: &mut [WideNode<N>], leaf: impl Fn(u32, u32) -> Aabb) {
+    for i in (0..nodes.len()).rev() {
+        for lane in 0..N {
+            let (first, count) = (nodes[i].first[lane], nodes[i].count[lane]);
+            let bbox = match count {
+                0 if first == 0 => continue,
+                0 => nodes[first as usize].boxes.bounds(),
+                count => leaf(first, count),
+            };
+            nodes[i].boxes.set(lane, bbox);
+        }
+    }
+}
+
+fn bin_index(x: f64, extent: Interval) -> usize {
+    let bin = ((x - extent.min()) / extent.size() * SAH_BINS as f64) as usize;
+    bin.min(SAH_BINS - 1)
+}
+
+fn merge(a: Option<Aabb>, b: Option<Aabb>) -> Option<Aabb> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(Aabb::from_bbox(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+// Move the objects matching `left` to the front, returning how many there are.
+fn partition(prims: &mut [Prim], left: impl Fn(&Prim) -> bool) -> usize {
+    let mut split = 0;
+    for i in 0..prims.len() {
+        if left(&prims[i]) {
+            prims.swap(i, split);
+            split += 1;
+        }
+    }
+    split
+}
+
+impl BvhNode {
+    fn hit_binary(&self, nodes: &[Node], r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        // Distance at which the ray enters the box of `node`, if it does before the
+        // closest hit so far.
+        let enter = |node: u32, closest: f64| {
+            let mut t = Interval::from(ray_t.min(), closest);
+            nodes[node as usize].bbox.hit(r, &mut t).then_some(t.min())
+        };
+        let Some(t) = nodes.first().and_then(|_| enter(0, ray_t.max())) else {
+            return false;
+        };
+
+        // Walk the tree with a fixed stack of nodes and their entry distances, going
+        // into the nearer child first so that the far one is often skipped.
+        let mut hit_anything = false;
+        let mut closest = ray_t.max();
+        let mut stack = [(0u32, 0.0); STACK_SIZE];
+        stack[0] = (0, t);
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let (index, t) = stack[len];
+            if t >= closest {
+                continue;
+            }
+            let node = nodes[index as usize];
+            if node.count == 0 {
+                let (left, right) = (node.first, node.first + 1);
+                match (enter(left, closest), enter(right, closest)) {
+                    (Some(t_left), Some(t_right)) => {
+                        let (near, far) = match t_left <= t_right {
+                            true => ((left, t_left), (right, t_right)),
+                            false => ((right, t_right), (left, t_left)),
+                        };
+                        stack[len] = far;
+                        stack[len + 1] = near;
+                        len += 2;
+                    }
+                    (Some(t_left), None) => {
+                        stack[len] = (left, t_left);
+                        len += 1;
+                    }
+                    (None, Some(t_right)) => {
+                        stack[len] = (right, t_right);
+                        len += 1;
+                    }
+                    (None, None) => {}
+                }
+                continue;
+            }
+            hit_anything |= self.hit_leaf(node.first, node.count, r, ray_t, &mut closest, rec);
+        }
+        hit_anything
+    }
+
+    fn hit_wide<const N: usize>(
+        &self,
+        nodes: &[WideNode<N>],
+        r: &Ray,
+        ray_t: Interval,
+        rec: &mut HitRecord,
+    ) -> bool {
+        if nodes.is_empty() || !self.bbox.hit(r, &mut ray_t.clone()) {
             return false;
         }
 
-        let hit_left = self
-            .left
-            .as_ref()
-            .map_or(false, |left| left.hit(r, ray_t, rec));
-        let hit_right = self.right.as_ref().map_or(false, |right| {
-            right.hit(
-                r,
-                Interval::from(
-                    ray_t.min(),
-                    match hit_left {
-                        true => rec.t,
-                        false => ray_t.max(),
-                    },
-                ),
-                rec,
-            )
-        });
-        hit_left || hit_right
+        // As for the binary tree, with the stack holding the children of a node
+        // that the ray enters, leaves or nodes, pushed from the farthest in.
+        let slab = SlabRay::from(r);
+        let mut hit_anything = false;
+        let mut closest = ray_t.max();
+        let mut stack = [(0u32, 0u32, 0.0); STACK_SIZE * 8];
+        stack[0] = (0, 0, ray_t.min());
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let (first, count, t) = stack[len];
+            if t >= closest {
+                continue;
+            }
+            if count > 0 {
+                hit_anything |= self.hit_leaf(first, count, r, ray_t, &mut closest, rec);
+                continue;
+            }
+
+            let node = &nodes[first as usize];
+            let (mut mask, entry) = node.boxes.hit(&slab, Interval::from(ray_t.min(), closest));
+            let mut hits = [(0.0, 0); N];
+            let mut n = 0;
+            while mask != 0 {
+                let i = mask.trailing_zeros() as usize;
+                mask &= mask - 1;
+                hits[n] = (entry[i], i);
+                n += 1;
+            }
+            hits[..n].sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+            for &(t, i) in &hits[..n] {
+                stack[len] = (node.first[i], node.count[i], t);
+                len += 1;
+            }
+        }
+        hit_anything
+    }
+
+    fn occluded_binary(&self, nodes: &[Node], r: &Ray, ray_t: Interval) -> bool {
+        // Any hit will do, so children are visited in stored order and the walk ends
+        // at the first object hit.
+        let mut stack = [0u32; STACK_SIZE];
+        let mut len = match nodes.is_empty() {
+            true => 0,
+            false => 1,
+        };
+        while len > 0 {
+            len -= 1;
+            let node = nodes[stack[len] as usize];
+            if !node.bbox.hit(r, &mut ray_t.clone()) {
+                continue;
+            }
+            if node.count == 0 {
+                stack[len] = node.first + 1;
+                stack[len + 1] = node.first;
+                len += 2;
+                continue;
+            }
+            if self.occluded_leaf(node.first, node.count, r, ray_t) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn occluded_wide<const N: usize>(
+        &self,
+        nodes: &[WideNode<N>],
+        r: &Ray,
+        ray_t: Interval,
+    ) -> bool {
+        if nodes.is_empty() || !self.bbox.hit(r, &mut ray_t.clone()) {
+            return false;
+        }
+        let slab = SlabRay::from(r);
+        let mut stack = [(0u32, 0u32); STACK_SIZE * 8];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let (first, count) = stack[len];
+            if count > 0 {
+                if self.occluded_leaf(first, count, r, ray_t) {
+                    return true;
+                }
+                continue;
+            }
+            let node = &nodes[first as usize];
+            let (mut mask, _) = node.boxes.hit(&slab, ray_t);
+            while mask != 0 {
+                let i = mask.trailing_zeros() as usize;
+                mask &= mask - 1;
+                stack[len] = (node.first[i], node.count[i]);
+                len += 1;
+            }
+        }
+        false
+    }
+
+    fn occluded_leaf(&self, first: u32, count: u32, r: &Ray, ray_t: Interval) -> bool {
+        let first = first as usize;
+        self.objects[first..first + count as usize]
+            .iter()
+            .any(|(_, object)| object.occluded(r, ray_t))
+    }
+
+    fn hit_leaf(
+        &self,
+        first: u32,
+        count: u32,
+        r: &Ray,
+        ray_t: Interval,
+        closest: &mut f64,
+        rec: &mut HitRecord,
+    ) -> bool {
+        let mut hit_anything = false;
+        let first = first as usize;
+        for (id, object) in &self.objects[first..first + count as usize] {
+            if object.hit(r, Interval::from(ray_t.min(), *closest), rec) {
+                hit_anything = true;
+                *closest = rec.t;
+                rec.object_id = *id;
+            }
+        }
+        hit_anything
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        match &self.tree {
+            Tree::Binary(nodes) => self.hit_binary(nodes, r, ray_t, rec),
+            Tree::Four(nodes) => self.hit_wide(nodes, r, ray_t, rec),
+            Tree::Eight(nodes) => self.hit_wide(nodes, r, ray_t, rec),
+        }
+    }
+    fn occluded(&self, r: &Ray, ray_t: Interval) -> bool {
+        match &self.tree {
+            Tree::Binary(nodes) => self.occluded_binary(nodes, r, ray_t),
+            Tree::Four(nodes) => self.occluded_wide(nodes, r, ray_t),
+            Tree::Eight(nodes) => self.occluded_wide(nodes, r, ray_t),
+        }
     }
     fn bounding_box(&self) -> Aabb {
         self.bbox
     }
+
+    // Boxes are refit bottom-up, keeping the tree as it is while it stays within
+    // `REBUILD_COST_RATIO` of its cost when built.
+    fn refit(&mut self, time: Interval) {
+        if self.objects.is_empty() {
+            return;
+        }
+        for (_, object) in self.objects.iter_mut() {
+            refit_object(object, time);
+        }
+        let objects = &self.objects;
+        let leaf = |first: u32, count: u32| {
+            let first = first as usize;
+            objects[first..first + count as usize]
+                .iter()
+                .map(|(_, object)| object.bounding_box())
+                .fold(aabb::EMPTY, Aabb::from_bbox)
+        };
+        self.bbox = match &mut self.tree {
+            Tree::Binary(nodes) => {
+                for i in (0..nodes.len()).rev() {
+                    let node = nodes[i];
+                    let first = node.first as usize;
+                    nodes[i].bbox = match node.count {
+                        0 => Aabb::from_bbox(nodes[first].bbox, nodes[first + 1].bbox),
+                        count => leaf(node.first, count),
+                    };
+                }
+                nodes[0].bbox
+            }
+            Tree::Four(nodes) => {
+                refit_wide(nodes, leaf);
+                nodes[0].boxes.bounds()
+            }
+            Tree::Eight(nodes) => {
+                refit_wide(nodes, leaf);
+                nodes[0].boxes.bounds()
+            }
+        };
+
+        let cost = self.sah_cost();
+        if self
+            .built_cost
+            .is_none_or(|built| cost > built * REBUILD_COST_RATIO)
+        {
+            self.rebuild();
+        }
+    }
+}
+
+impl Default for BvhNode {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -1,43 +1,73 @@
-use crate::color::{write_colors, Color};
+use crate::aov::Aov;
+use crate::color::Color;
+use crate::error::{Error, Result};
+use crate::framebuffer::Framebuffer;
 use crate::hittable::{HitRecord, Hittable};
 use crate::interval::Interval;
 use crate::ray::Ray;
-use crate::rtweekend::{degrees2radians, random_double, INF};
+use crate::rtweekend::{degrees2radians, mix_seed, random_double, seed_random, DEFAULT_SEED, INF};
 use crate::vec3::{Point3, Vec3};
 
-use kdam::tqdm;
+use kdam::{tqdm, BarExt};
+use std::sync::Mutex;
+use std::thread;
 
+#[derive(Clone)]
 pub struct Camera {
     pub aspect_ratio: f64,
     pub image_width: i32,
     pub samples_per_pixel: i32,
     pub max_depth: i32,
-    pub vfov: i32,
+    pub vfov: f64,
     pub lookfrom: Point3, // Point camera is looking from
     pub lookat: Point3,   // Point camera is looking at
     pub vup: Vec3,        // Camera-relative "up" direction
     pub defocus_angle: f64,
     pub focus_dist: f64,
     pub backround: Color,
+    pub seed: u64,                      // Base seed for the per-pixel sample streams
+    pub threads: usize,                 // Number of render threads, 0 uses every available core
+    pub aovs: Vec<Aov>,                 // Auxiliary passes recorded next to the beauty image
+    pub shutter_open: f64,              // Ray times are drawn between the shutter opening
+    pub shutter_close: f64,             // and closing, in the time of moving objects
+    pub shutter_curve: Vec<(f64, f64)>, // (position in [0, 1], weight) pairs, empty when flat
+    pub motion: Vec<CameraKeyframe>,    // Poses during the exposure, empty when still
     image_height: i32,
+    viewport_width: f64,
+    viewport_height: f64,
+    defocus_radius: f64,
+    shutter_cdf: Vec<f64>, // Area of the shutter curve up to each of its points
+    view: View,
+}
+
+// Where the camera is at one point of the exposure. Poses in between keyframes are
+// interpolated linearly, and hold still before the first and after the last.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKeyframe {
+    pub time: f64,
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+}
+
+// Viewport of the camera at one pose.
+#[derive(Debug, Clone, Copy, Default)]
+struct View {
+    center: Point3,
     pixel00_loc: Point3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
-    camera_center: Point3,
-    u: Vec3,
-    v: Vec3,
-    w: Vec3,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         aspect_ratio: f64,
         image_width: i32,
         samples_per_pixel: i32,
         max_depth: i32,
-        vfov: i32,
+        vfov: f64,
         lookfrom: Point3,
         lookat: Point3,
         vup: Vec3,
@@ -57,35 +87,184 @@ impl Camera {
             defocus_angle,
             focus_dist,
             backround,
+            seed: DEFAULT_SEED,
+            threads: 0,
+            aovs: Vec::new(),
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            shutter_curve: Vec::new(),
+            motion: Vec::new(),
             image_height: 0,
-            camera_center: Point3::zeros(),
-            pixel00_loc: Point3::zeros(),
-            pixel_delta_u: Vec3::zeros(),
-            pixel_delta_v: Vec3::zeros(),
-            u: Vec3::zeros(),
-            v: Vec3::zeros(),
-            w: Vec3::zeros(),
-            defocus_disk_u: Vec3::zeros(),
-            defocus_disk_v: Vec3::zeros(),
+            viewport_width: 0.0,
+            viewport_height: 0.0,
+            defocus_radius: 0.0,
+            shutter_cdf: Vec::new(),
+            view: View::default(),
+        }
+    }
+
+    // Reject settings that would leave the view undefined, before rendering starts.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| Err(Error::InvalidCamera(reason.to_string()));
+        if self.image_width < 1 {
+            return invalid("image_width must be at least 1");
+        }
+        if !(self.aspect_ratio > 0.0 && self.aspect_ratio.is_finite()) {
+            return invalid("aspect_ratio must be positive");
+        }
+        if self.samples_per_pixel < 1 {
+            return invalid("samples_per_pixel must be at least 1");
+        }
+        if !(self.vfov > 0.0 && self.vfov < 180.0) {
+            return invalid("vfov must be between 0 and 180 degrees");
+        }
+        self.validate_view(self.lookfrom, self.lookat)?;
+        for (i, key) in self.motion.iter().enumerate() {
+            if !key.time.is_finite() {
+                return invalid(&format!("motion[{}]: time must be finite", i));
+            }
+            self.validate_view(key.lookfrom, key.lookat)
+                .map_err(|e| e.within(format!("motion[{}]", i)))?;
+        }
+        if !(self.focus_dist > 0.0 && self.focus_dist.is_finite()) {
+            return invalid("focus_dist must be positive");
+        }
+        if !(0.0..180.0).contains(&self.defocus_angle) {
+            return invalid("defocus_angle must be between 0 and 180 degrees");
+        }
+        if !(self.shutter_open.is_finite() && self.shutter_close.is_finite())
+            || self.shutter_open > self.shutter_close
+        {
+            return invalid("the shutter must open before it closes");
         }
+        if !self.shutter_curve.is_empty() {
+            let curve = &self.shutter_curve;
+            if curve.len() < 2 || curve[0].0 != 0.0 || curve[curve.len() - 1].0 != 1.0 {
+                return invalid("shutter_curve must run from position 0 to position 1");
+            }
+            if curve.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                return invalid("shutter_curve positions must increase");
+            }
+            if curve.iter().any(|(_, w)| !(*w >= 0.0 && w.is_finite())) {
+                return invalid("shutter_curve weights must be positive");
+            }
+            if curve.iter().all(|(_, w)| *w == 0.0) {
+                return invalid("shutter_curve never opens");
+            }
+        }
+        Ok(())
     }
 
-    pub fn render<T: Hittable>(&mut self, world: &T) {
+    fn validate_view(&self, lookfrom: Point3, lookat: Point3) -> Result<()> {
+        let invalid = |reason: &str| Err(Error::InvalidCamera(reason.to_string()));
+        let view = lookat - lookfrom;
+        if view.near_zero() {
+            return invalid("lookfrom and lookat are the same point");
+        }
+        if self.vup.cross(view).length() <= 1e-8 * self.vup.length() * view.length() {
+            return invalid("vup is parallel to the view direction");
+        }
+        Ok(())
+    }
+
+    pub fn render<T: Hittable>(&mut self, world: &T) -> Framebuffer {
         self.initialize();
-        println!("P3\n{} {}\n255", self.image_width, self.image_height);
+        let width = self.image_width as usize;
+        let height = self.image_height as usize;
+        let mut image = Framebuffer::new(width, height);
+        let mut passes: Vec<Framebuffer> = self
+            .aovs
+            .iter()
+            .map(|_| Framebuffer::new(width, height))
+            .collect();
+
+        {
+            // Workers pull whole rows, with the matching row of every pass, from a
+            // shared queue so that slow rows do not stall a statically assigned band.
+            let mut pass_rows: Vec<_> = passes.iter_mut().map(|p| p.rows_mut()).collect();
+            let rows = Mutex::new(image.rows_mut().enumerate().map(move |(j, row)| {
+                let pass_row: Vec<&mut [Color]> =
+                    pass_rows.iter_mut().map(|r| r.next().unwrap()).collect();
+                (j, row, pass_row)
+            }));
+            let pb = Mutex::new(tqdm!(total = height));
+            let camera = &*self;
+
+            thread::scope(|s| {
+                for _ in 0..camera.thread_count() {
+                    s.spawn(|| {
+                        let mut samples = vec![Color::zeros(); camera.aovs.len()];
+                        loop {
+                            let next = rows.lock().unwrap().next();
+                            let Some((j, row, mut pass_row)) = next else {
+                                break;
+                            };
+                            for (i, pixel) in row.iter_mut().enumerate() {
+                                *pixel =
+                                    camera.render_pixel(i as i32, j as i32, world, &mut samples);
+                                for (pass, sample) in pass_row.iter_mut().zip(&samples) {
+                                    pass[i] = *sample;
+                                }
+                            }
+                            pb.lock().unwrap().update(1);
+                        }
+                    });
+                }
+            });
+        }
+
+        for (aov, pass) in self.aovs.iter().zip(passes) {
+            image.add_aov(*aov, pass);
+        }
+        image
+    }
+
+    fn thread_count(&self) -> usize {
+        match self.threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        }
+    }
 
-        for j in tqdm!(0..self.image_height) {
-            for i in 0..self.image_width {
-                let mut pixel_color = Color::zeros();
+    fn render_pixel<T: Hittable>(&self, i: i32, j: i32, world: &T, aovs: &mut [Color]) -> Color {
+        // Each pixel draws from its own stream, so the image does not depend on
+        // which thread rendered it or in what order.
+        seed_random(mix_seed(self.seed, (j * self.image_width + i) as u64));
 
-                for _ in 0..self.samples_per_pixel {
-                    let r = self.get_ray(i, j);
-                    pixel_color += Camera::ray_color(&self, &r, self.max_depth, world);
+        let mut pixel_color = Color::zeros();
+        aovs.fill(Color::zeros());
+        for s in 0..self.samples_per_pixel {
+            let r = self.get_ray(i, j);
+            if self.aovs.is_empty() {
+                pixel_color += self.ray_color(&r, self.max_depth, world);
+                continue;
+            }
+
+            // Trace the first hit here so the passes can be recorded from it.
+            let mut rec = HitRecord::new();
+            let first_hit = world
+                .hit(&r, Interval::from(0.001, INF), &mut rec)
+                .then_some(&rec);
+            for (sample, aov) in aovs.iter_mut().zip(&self.aovs) {
+                if aov.is_filtered() {
+                    *sample += aov.sample(&r, first_hit);
+                } else if s == 0 {
+                    *sample = aov.sample(&r, first_hit);
                 }
-                write_colors(pixel_color, self.samples_per_pixel);
+            }
+            pixel_color += match first_hit {
+                _ if self.max_depth <= 0 => Color::zeros(),
+                Some(rec) => self.hit_color(&r, rec, self.max_depth, world),
+                None => self.backround,
+            };
+        }
+        for (sample, aov) in aovs.iter_mut().zip(&self.aovs) {
+            if aov.is_filtered() {
+                *sample = *sample / self.samples_per_pixel;
             }
         }
-        print!("Done");
+        // Average the samples, keeping the radiance linear and unclamped.
+        pixel_color / self.samples_per_pixel
     }
 
     fn initialize(&mut self) {
@@ -95,36 +274,90 @@ impl Camera {
             _ => self.image_height,
         };
 
-        self.camera_center = self.lookfrom;
-        let theta = degrees2radians(self.vfov as f64);
+        let theta = degrees2radians(self.vfov);
         let height = (theta / 2.0).tan();
-        let viewport_height = 2.0 * height * self.focus_dist as f64;
-        let viewport_width = viewport_height * (self.image_width as f64 / self.image_height as f64);
+        self.viewport_height = 2.0 * height * self.focus_dist;
+        self.viewport_width =
+            self.viewport_height * (self.image_width as f64 / self.image_height as f64);
+        self.defocus_radius = degrees2radians(self.defocus_angle / 2.0).tan() * self.focus_dist;
+        self.view = self.view_from(self.lookfrom, self.lookat);
 
+        self.motion.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.shutter_cdf = vec![0.0];
+        for pair in self.shutter_curve.windows(2) {
+            let area = (pair[0].1 + pair[1].1) / 2.0 * (pair[1].0 - pair[0].0);
+            self.shutter_cdf
+                .push(self.shutter_cdf.last().unwrap() + area);
+        }
+    }
+
+    fn view_from(&self, lookfrom: Point3, lookat: Point3) -> View {
         // Calculate the u,v,w unit basis vectors for the camera coordinate frame.
-        self.w = (self.lookfrom - self.lookat).unit_vector();
-        self.u = self.vup.cross(self.w).unit_vector();
-        self.v = self.w.cross(self.u);
+        let w = (lookfrom - lookat).unit_vector();
+        let u = self.vup.cross(w).unit_vector();
+        let v = w.cross(u);
 
         // Calculate the vectors across the horizontal and down the vertical viewport edges.
-        let viewport_u = self.u * viewport_width; // Vector across viewport horizontal edge
-        let viewport_v = self.v * -viewport_height; // Vector down viewport vertical edge
+        let viewport_u = u * self.viewport_width; // Vector across viewport horizontal edge
+        let viewport_v = v * -self.viewport_height; // Vector down viewport vertical edge
 
         // Calculate the horizontal and vertical delta vectors from pixel to pixel.
-        self.pixel_delta_u = viewport_u / self.image_width;
-        self.pixel_delta_v = viewport_v / self.image_height;
+        let pixel_delta_u = viewport_u / self.image_width;
+        let pixel_delta_v = viewport_v / self.image_height;
 
         // Calculate the location of the upper left pixel.
-        let viewport_uper_left = self.camera_center
-            - (self.w * self.focus_dist as f64)
-            - viewport_u / 2
-            - viewport_v / 2;
-        self.pixel00_loc = viewport_uper_left + (self.pixel_delta_u - self.pixel_delta_v) * 0.5;
-
-        let defocus_radius =
-            degrees2radians(self.defocus_angle as f64 / 2.0).tan() * self.focus_dist as f64;
-        self.defocus_disk_u = self.u * defocus_radius;
-        self.defocus_disk_v = self.v * defocus_radius;
+        let viewport_uper_left = lookfrom - (w * self.focus_dist) - viewport_u / 2 - viewport_v / 2;
+        View {
+            center: lookfrom,
+            pixel00_loc: viewport_uper_left + (pixel_delta_u - pixel_delta_v) * 0.5,
+            pixel_delta_u,
+            pixel_delta_v,
+            defocus_disk_u: u * self.defocus_radius,
+            defocus_disk_v: v * self.defocus_radius,
+        }
+    }
+
+    // The view at `time`, following the motion keyframes when there are any.
+    fn view_at(&self, time: f64) -> View {
+        let k = &self.motion;
+        let (lookfrom, lookat) = match k.partition_point(|key| key.time <= time) {
+            _ if k.is_empty() => return self.view,
+            0 => (k[0].lookfrom, k[0].lookat),
+            n if n == k.len() => (k[n - 1].lookfrom, k[n - 1].lookat),
+            n => {
+                let (a, b) = (&k[n - 1], &k[n]);
+                let s = (time - a.time) / (b.time - a.time);
+                (
+                    a.lookfrom * (1.0 - s) + b.lookfrom * s,
+                    a.lookat * (1.0 - s) + b.lookat * s,
+                )
+            }
+        };
+        self.view_from(lookfrom, lookat)
+    }
+
+    // Draw a ray time, with a density following the shutter curve over the interval.
+    fn shutter_time(&self) -> f64 {
+        let s = random_double();
+        let position = match self.shutter_curve.len() {
+            0 => s,
+            _ => {
+                // Invert the cumulative area of the piecewise linear curve.
+                let cdf = &self.shutter_cdf;
+                let area = s * cdf[cdf.len() - 1];
+                let i = (cdf.partition_point(|&a| a <= area).max(1) - 1).min(cdf.len() - 2);
+                let ((x0, w0), (x1, w1)) = (self.shutter_curve[i], self.shutter_curve[i + 1]);
+                let rest = area - cdf[i];
+                let slope = (w1 - w0) / (x1 - x0);
+                let root = (w0 * w0 + 2.0 * slope * rest).max(0.0).sqrt();
+                let d = match w0 + root {
+                    sum if sum > 0.0 => 2.0 * rest / sum,
+                    _ => 0.0,
+                };
+                (x0 + d).min(x1)
+            }
+        };
+        self.shutter_open + (self.shutter_close - self.shutter_open) * position
     }
 
     fn ray_color<T: Hittable>(&self, r: &Ray, depth: i32, world: &T) -> Color {
@@ -136,10 +369,14 @@ impl Camera {
         if !world.hit(r, Interval::from(0.001, INF), &mut rec) {
             return self.backround;
         }
+        self.hit_color(r, &rec, depth, world)
+    }
+
+    fn hit_color<T: Hittable>(&self, r: &Ray, rec: &HitRecord, depth: i32, world: &T) -> Color {
         let mut scattered = Ray::new();
         let mut attenuation = Color::ones();
         let color_from_emission = rec.mat.emitted(rec.u, rec.v, &rec.p);
-        if !rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
+        if !rec.mat.scatter(r, rec, &mut attenuation, &mut scattered) {
             return color_from_emission;
         }
 
@@ -148,29 +385,25 @@ impl Camera {
     }
 
     fn get_ray(&self, i: i32, j: i32) -> Ray {
+        // Every sample is drawn before the time, which moving cameras need first.
+        let px = -0.5 + random_double();
+        let py = -0.5 + random_double();
+        let disk = (self.defocus_angle > 0.0).then(Vec3::random_in_unit_disk);
+        let ray_time = self.shutter_time();
+        let view = self.view_at(ray_time);
+
         let pixel_center =
-            self.pixel00_loc + (self.pixel_delta_u * i as f64) + (self.pixel_delta_v * j as f64);
-        let pixel_sample = pixel_center + self.pixel_sample_square();
+            view.pixel00_loc + (view.pixel_delta_u * i as f64) + (view.pixel_delta_v * j as f64);
+        let pixel_sample = pixel_center + ((view.pixel_delta_u * px) + (view.pixel_delta_v * py));
 
-        let ray_origin = match self.defocus_angle <= 0.0 {
-            true => self.camera_center,
-            false => self.defocus_disk_sample(),
+        let ray_origin = match disk {
+            None => view.center,
+            Some(p) => view.center + (view.defocus_disk_u * p[0]) + (view.defocus_disk_v * p[1]),
         };
         let ray_direction = pixel_sample - ray_origin;
-        let ray_time = random_double();
 
         Ray::from(ray_origin, ray_direction, ray_time)
     }
-
-    fn pixel_sample_square(&self) -> Vec3 {
-        let px = -0.5 + random_double();
-        let py = -0.5 + random_double();
-        (self.pixel_delta_u * px) + (self.pixel_delta_v * py)
-    }
-    fn defocus_disk_sample(&self) -> Point3 {
-        let p = Vec3::random_in_unit_disk();
-        self.camera_center + (self.defocus_disk_u * p[0]) + (self.defocus_disk_v * p[1])
-    }
 }
 
 impl Default for Camera {
@@ -180,23 +413,26 @@ impl Default for Camera {
             image_width: 400,
             samples_per_pixel: 100,
             max_depth: 50,
-            vfov: 20,
+            vfov: 20.0,
             lookfrom: Point3::from(13.0, 2.0, 3.0),
             lookat: Point3::from(0.0, 0.0, 0.0),
             vup: Vec3::Y(false),
             defocus_angle: 0.0,
             focus_dist: 10.0,
             backround: Color::from(0.7, 0.8, 1.0),
+            seed: DEFAULT_SEED,
+            threads: 0,
+            aovs: Vec::new(),
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            shutter_curve: Vec::new(),
+            motion: Vec::new(),
             image_height: 0,
-            pixel00_loc: Point3::zeros(),
-            pixel_delta_u: Point3::zeros(),
-            pixel_delta_v: Point3::zeros(),
-            camera_center: Point3::zeros(),
-            u: Vec3::zeros(),
-            v: Vec3::zeros(),
-            w: Vec3::zeros(),
-            defocus_disk_u: Vec3::zeros(),
-            defocus_disk_v: Vec3::zeros(),
+            viewport_width: 0.0,
+            viewport_height: 0.0,
+            defocus_radius: 0.0,
+            shutter_cdf: Vec::new(),
+            view: View::default(),
         }
     }
 }
@@ -0,0 +1,206 @@
+use std::str::FromStr;
+
+use ray_tracer_rs::animation::Animation;
+use ray_tracer_rs::aov::Aov;
+use ray_tracer_rs::bvh::{BvhSplit, BvhWidth};
+use ray_tracer_rs::camera::Camera;
+
+pub const USAGE: &str = "\
+Usage: ray_tracer_rs [SCENE] [OPTIONS]
+
+Renders one of the built-in scenes, final_scene_preview when none is given.
+
+Options:
+  -l, --list                 List the built-in scenes and exit
+  -f, --file <FILE>          Render the JSON scene description or the glTF/GLB
+                             scene in FILE instead
+  -o, --output <FILE>        Save to FILE (png, jpg, ppm, pfm, hdr or exr),
+                             otherwise a plain text PPM is written to stdout
+  -w, --width <PIXELS>       Image width
+      --aspect-ratio <RATIO> Width over height, as a number or as W/H
+  -s, --samples <N>          Samples per pixel
+  -d, --max-depth <N>        Maximum number of bounces
+      --vfov <DEGREES>       Vertical field of view
+      --seed <N>             Seed for the scene and the pixel samples (default 0)
+  -t, --threads <N>          Render threads, 0 uses every core
+      --aov <PASSES>         Comma separated passes to record next to the image
+                             (depth, normal, position, albedo, object_id)
+      --bvh <SPLIT>          How BVHs divide their objects: median (default) or
+                             sah, for the surface area heuristic
+      --bvh-width <N>        Children per BVH node: 2 (default), 4 or 8, the
+                             boxes of wide nodes being tested together
+      --frames <A-B>         Render frames A to B of an animation, each to the
+                             --output name numbered where its # run is, skipping
+                             frames whose file already exists
+      --fps <N>              Frames per second of the animation (default 24)
+  -h, --help                 Print this help";
+
+// Options given on the command line, `None` keeping the value set by the scene.
+#[derive(Debug, Default)]
+pub struct Options {
+    pub scene: Option<String>,
+    pub scene_file: Option<String>,
+    pub list: bool,
+    pub help: bool,
+    pub output: Option<String>,
+    pub image_width: Option<i32>,
+    pub aspect_ratio: Option<f64>,
+    pub samples_per_pixel: Option<i32>,
+    pub max_depth: Option<i32>,
+    pub vfov: Option<f64>,
+    pub seed: Option<u64>,
+    pub threads: Option<usize>,
+    pub aovs: Vec<Aov>,
+    pub bvh: Option<BvhSplit>,
+    pub bvh_width: Option<BvhWidth>,
+    pub frames: Option<(u32, u32)>,
+    pub fps: Option<f64>,
+}
+
+impl Options {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            // Accept both `--flag value` and `--flag=value`.
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or(format!("missing value for {}", flag))
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => options.help = true,
+                "-l" | "--list" => options.list = true,
+                "-f" | "--file" => options.scene_file = Some(value()?),
+                "-o" | "--output" => options.output = Some(value()?),
+                "-w" | "--width" => options.image_width = Some(parse(&flag, &value()?)?),
+                "--aspect-ratio" => options.aspect_ratio = Some(parse_ratio(&value()?)?),
+                "-s" | "--samples" => options.samples_per_pixel = Some(parse(&flag, &value()?)?),
+                "-d" | "--max-depth" => options.max_depth = Some(parse(&flag, &value()?)?),
+                "--vfov" => options.vfov = Some(parse(&flag, &value()?)?),
+                "--seed" => options.seed = Some(parse(&flag, &value()?)?),
+                "-t" | "--threads" => options.threads = Some(parse(&flag, &value()?)?),
+                "--aov" => {
+                    for name in value()?.split(',') {
+                        let aov = Aov::from_name(name).ok_or(format!("unknown pass: {}", name))?;
+                        options.aovs.push(aov);
+                    }
+                }
+                "--bvh" => {
+                    let name = value()?;
+                    let split =
+                        BvhSplit::from_name(&name).ok_or(format!("unknown split: {}", name))?;
+                    options.bvh = Some(split);
+                }
+                "--bvh-width" => {
+                    let name = value()?;
+                    let width =
+                        BvhWidth::from_name(&name).ok_or(format!("unknown BVH width: {}", name))?;
+                    options.bvh_width = Some(width);
+                }
+                "--frames" => options.frames = Some(parse_frames(&value()?)?),
+                "--fps" => options.fps = Some(parse(&flag, &value()?)?),
+                _ if flag.starts_with('-') => return Err(format!("unknown option: {}", flag)),
+                _ if options.scene.is_none() => options.scene = Some(arg),
+                _ => return Err(format!("unexpected argument: {}", arg)),
+            }
+        }
+
+        if options.scene.is_some() && options.scene_file.is_some() {
+            return Err("a built-in scene and a scene file cannot both be given".to_string());
+        }
+        if options.image_width.is_some_and(|w| w < 1) {
+            return Err("the image width must be at least 1".to_string());
+        }
+        if options.samples_per_pixel.is_some_and(|s| s < 1) {
+            return Err("at least one sample per pixel is needed".to_string());
+        }
+        if options
+            .aspect_ratio
+            .is_some_and(|r| r <= 0.0 || !r.is_finite())
+        {
+            return Err("the aspect ratio must be positive".to_string());
+        }
+        if options
+            .fps
+            .is_some_and(|fps| fps <= 0.0 || !fps.is_finite())
+        {
+            return Err("the frame rate must be positive".to_string());
+        }
+        Ok(options)
+    }
+
+    // Apply the overrides on top of the camera set up by a scene.
+    pub fn apply(&self, cam: &mut Camera) {
+        if let Some(image_width) = self.image_width {
+            cam.image_width = image_width;
+        }
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            cam.aspect_ratio = aspect_ratio;
+        }
+        if let Some(samples_per_pixel) = self.samples_per_pixel {
+            cam.samples_per_pixel = samples_per_pixel;
+        }
+        if let Some(max_depth) = self.max_depth {
+            cam.max_depth = max_depth;
+        }
+        if let Some(vfov) = self.vfov {
+            cam.vfov = vfov;
+        }
+        if let Some(seed) = self.seed {
+            cam.seed = seed;
+        }
+        if let Some(threads) = self.threads {
+            cam.threads = threads;
+        }
+        cam.aovs = self.aovs.clone();
+    }
+
+    // Apply the frame range and rate on top of the animation of a scene.
+    pub fn apply_animation(&self, animation: &mut Animation) {
+        if let Some((first, last)) = self.frames {
+            animation.first_frame = first;
+            animation.last_frame = last;
+        }
+        if let Some(fps) = self.fps {
+            animation.fps = fps;
+        }
+    }
+}
+
+fn parse<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+// A single frame or an inclusive range of frames, as `A-B`.
+fn parse_frames(value: &str) -> Result<(u32, u32), String> {
+    let (first, last) = match value.split_once('-') {
+        Some((first, last)) => (parse("--frames", first)?, parse("--frames", last)?),
+        None => {
+            let frame = parse("--frames", value)?;
+            (frame, frame)
+        }
+    };
+    if first > last {
+        return Err(format!("the frame range {} ends before it starts", value));
+    }
+    Ok((first, last))
+}
+
+fn parse_ratio(value: &str) -> Result<f64, String> {
+    match value.split_once('/') {
+        Some((w, h)) => Ok(parse::<f64>("--aspect-ratio", w)? / parse::<f64>("--aspect-ratio", h)?),
+        None => parse("--aspect-ratio", value),
+    }
+}
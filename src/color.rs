@@ -1,31 +1,31 @@
+use std::io::{self, Write};
+
 use crate::{interval::Interval, vec3::Vec3};
 
 pub type Color = Vec3;
 
-pub fn write_colors(pixel_color: Color, samples_per_pixel: i32) {
-    let mut r = pixel_color.x();
-    let mut g = pixel_color.y();
-    let mut b = pixel_color.z();
-
-    // Divide the color by the number of samples.
-    let scale = 1.0 / samples_per_pixel as f64;
-    r *= scale;
-    g *= scale;
-    b *= scale;
-
+pub fn to_rgb8(pixel_color: Color) -> [u8; 3] {
     // Apply the linear to gamma transform.
-    r = linear2gamma(r);
-    g = linear2gamma(g);
-    b = linear2gamma(b);
+    let r = linear2gamma(pixel_color.x());
+    let g = linear2gamma(pixel_color.y());
+    let b = linear2gamma(pixel_color.z());
+    to_linear_rgb8(Color::from(r, g, b))
+}
 
-    // Write the translated [0,255] value of each color component.
+// The same without the gamma curve, for values that are data rather than colors.
+pub fn to_linear_rgb8(pixel_color: Color) -> [u8; 3] {
+    // Translate to the [0,255] value of each color component.
     let intensity = Interval::from(0.000, 0.999);
-    println!(
-        "{} {} {}",
-        (256.0 * intensity.clamp(r)) as i32,
-        (256.0 * intensity.clamp(g)) as i32,
-        (256.0 * intensity.clamp(b)) as i32
-    );
+    [
+        (256.0 * intensity.clamp(pixel_color.x())) as u8,
+        (256.0 * intensity.clamp(pixel_color.y())) as u8,
+        (256.0 * intensity.clamp(pixel_color.z())) as u8,
+    ]
+}
+
+pub fn write_colors<W: Write>(out: &mut W, pixel_color: Color) -> io::Result<()> {
+    let [r, g, b] = to_rgb8(pixel_color);
+    writeln!(out, "{} {} {}", r, g, b)
 }
 
 fn linear2gamma(linear_copenant: f64) -> f64 {
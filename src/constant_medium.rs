@@ -1,96 +1,173 @@
 use crate::{
     color::Color,
-    hittable::{HitRecord, Hittable},
+    hittable::{refit_object, HitRecord, Hittable},
     interval::{Interval, UNIVERSE},
     material::{Isotropic, Material},
-    rtweekend::{random_double, INF},
+    ray::Ray,
+    rtweekend::{hash_double, random_double, INF},
     texture::Texture,
     vec3::Vec3,
 };
-use std::{marker::PhantomData, rc::Rc};
+use std::sync::Arc;
 
 pub struct ConstantMedium {
-    boundaries: Rc<dyn Hittable>,
+    boundaries: Arc<dyn Hittable>,
     neg_inv_density: f64,
-    phase_function: Rc<dyn Material>,
+    phase_function: Arc<dyn Material>,
 }
 
 impl ConstantMedium {
     pub fn from_texture(
-        boundaries: Rc<dyn Hittable>,
+        boundaries: Arc<dyn Hittable>,
         neg_inv_density: f64,
-        texture: Rc<dyn Texture>,
+        texture: Arc<dyn Texture>,
     ) -> Self {
         Self {
             boundaries,
             neg_inv_density: -1.0 / neg_inv_density,
-            phase_function: Rc::new(Isotropic::from_texture(texture)),
+            phase_function: Arc::new(Isotropic::from_texture(texture)),
         }
     }
-    pub fn from_color(boundaries: Rc<dyn Hittable>, neg_inv_density: f64, color: Color) -> Self {
+    pub fn from_color(boundaries: Arc<dyn Hittable>, neg_inv_density: f64, color: Color) -> Self {
         Self {
             boundaries,
             neg_inv_density: -1.0 / neg_inv_density,
-            phase_function: Rc::new(Isotropic::from_color(color)),
+            phase_function: Arc::new(Isotropic::from_color(color)),
         }
     }
 }
 
-impl Hittable for ConstantMedium {
-    fn hit(
-        &self,
-        r: &crate::ray::Ray,
-        ray_t: crate::interval::Interval,
-        rec: &mut crate::hittable::HitRecord,
-    ) -> bool {
-        // let enable_debug = false;
-        // let debugging = enable_debug && random_double() < 0.00001;
-
-        let mut rec1 = HitRecord::new();
-        let mut rec2 = HitRecord::new();
-
-        if !self.boundaries.hit(r, UNIVERSE, &mut rec1) {
-            return false;
+impl ConstantMedium {
+    // Where the ray enters and leaves the boundaries, clipped to `ray_t`.
+    fn span(&self, r: &Ray, ray_t: Interval) -> Option<(f64, f64)> {
+        let mut rec = HitRecord::new();
+        if !self.boundaries.hit(r, UNIVERSE, &mut rec) {
+            return None;
         }
+        let mut t1 = rec.t;
         if !self
             .boundaries
-            .hit(r, Interval::from(rec1.t + 0.00001, INF), &mut rec2)
+            .hit(r, Interval::from(t1 + 0.00001, INF), &mut rec)
         {
-            return false;
+            return None;
         }
+        let mut t2 = rec.t;
 
-        if rec1.t < ray_t.min() {
-            rec1.t = ray_t.min()
+        if t1 < ray_t.min() {
+            t1 = ray_t.min()
         }
-        if rec2.t > ray_t.max() {
-            rec2.t = ray_t.max()
+        if t2 > ray_t.max() {
+            t2 = ray_t.max()
         }
 
-        if rec1.t >= rec2.t {
-            return false;
+        if t1 >= t2 {
+            return None;
         }
 
-        if rec1.t <= 0.0 {
-            rec1.t = 0.0;
+        if t1 <= 0.0 {
+            t1 = 0.0;
         }
+        Some((t1, t2))
+    }
+
+    // How far the ray goes inside before scattering, given a uniform draw in [0, 1).
+    fn hit_distance(&self, draw: f64) -> f64 {
+        self.neg_inv_density * draw.ln()
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(
+        &self,
+        r: &crate::ray::Ray,
+        ray_t: crate::interval::Interval,
+        rec: &mut crate::hittable::HitRecord,
+    ) -> bool {
+        let Some((t1, t2)) = self.span(r, ray_t) else {
+            return false;
+        };
 
         let ray_length = r.direction().length();
-        let distance_inside_boundaries = (rec2.t - rec1.t) * ray_length;
-        let hit_distance = self.neg_inv_density * random_double().ln();
+        let distance_inside_boundaries = (t2 - t1) * ray_length;
+        let hit_distance = self.hit_distance(random_double());
 
         if hit_distance > distance_inside_boundaries {
             return false;
         }
 
-        rec.t = rec1.t + hit_distance / ray_length;
+        rec.t = t1 + hit_distance / ray_length;
         rec.p = r.at(rec.t);
 
         rec.normal = Vec3::zeros();
         rec.front_face = true;
         rec.mat = self.phase_function.clone();
+        rec.color = None;
         true
     }
+    // Whether a ray scatters depends on the distance it travels inside. It is drawn
+    // from a hash of the ray rather than from the stream of the thread, so that a
+    // query leaves the hits that follow it unchanged.
+    fn occluded(&self, r: &crate::ray::Ray, ray_t: Interval) -> bool {
+        let Some((t1, t2)) = self.span(r, ray_t) else {
+            return false;
+        };
+        let (origin, direction) = (r.origin(), r.direction());
+        let draw = hash_double(&[
+            origin.x(),
+            origin.y(),
+            origin.z(),
+            direction.x(),
+            direction.y(),
+            direction.z(),
+            r.time(),
+        ]);
+        self.hit_distance(draw) <= (t2 - t1) * direction.length()
+    }
     fn bounding_box(&self) -> crate::aabb::Aabb {
         self.boundaries.bounding_box()
     }
+    fn refit(&mut self, time: Interval) {
+        refit_object(&mut self.boundaries, time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtweekend::seed_random;
+    use crate::sphere::Sphere;
+    use crate::vec3::Point3;
+
+    fn fog(density: f64) -> ConstantMedium {
+        let boundary = Sphere::new_stationnary(
+            Point3::zeros(),
+            1.0,
+            Arc::new(Isotropic::from_color(Color::ones())),
+        );
+        ConstantMedium::from_color(Arc::new(boundary), density, Color::ones())
+    }
+
+    #[test]
+    fn occluded_leaves_the_random_stream_alone() {
+        let medium = fog(0.5);
+        seed_random(3);
+        let expected = random_double();
+        seed_random(3);
+        for i in 0..10 {
+            let origin = Point3::from(-5.0, i as f64 * 0.1, 0.0);
+            let r = Ray::from(origin, Vec3::from(1.0, 0.0, 0.0), 0.0);
+            medium.occluded(&r, Interval::from(0.001, INF));
+        }
+        assert_eq!(random_double(), expected);
+    }
+
+    #[test]
+    fn occluded_through_dense_fog_only() {
+        let r = Ray::from(Point3::from(-5.0, 0.0, 0.0), Vec3::from(1.0, 0.0, 0.0), 0.0);
+        assert!(fog(1e6).occluded(&r, Interval::from(0.001, INF)));
+        // Stopping short of the fog.
+        assert!(!fog(1e6).occluded(&r, Interval::from(0.001, 3.0)));
+        let above = Ray::from(Point3::from(-5.0, 2.0, 0.0), Vec3::from(1.0, 0.0, 0.0), 0.0);
+        assert!(!fog(1e6).occluded(&above, Interval::from(0.001, INF)));
+    }
 }
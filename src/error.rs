@@ -0,0 +1,108 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// Everything that can go wrong while loading a scene or saving an image, reported
+// before any rendering time is spent whenever possible.
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    Exr {
+        path: PathBuf,
+        source: exr::error::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    Gltf {
+        path: PathBuf,
+        source: gltf::Error,
+    },
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Malformed {
+        path: PathBuf,
+        message: String,
+    },
+    UnsupportedFormat(PathBuf),
+    Undefined {
+        kind: &'static str,
+        name: String,
+    },
+    CyclicTexture(String),
+    InvalidTexture(String),
+    InvalidMaterial(String),
+    DegenerateGeometry(String),
+    InvalidCamera(String),
+    InvalidAnimation(String),
+    // Names the scene item, e.g. `materials.glass` or `objects[3]`, an error comes from.
+    In {
+        name: String,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    pub fn within<S: Into<String>>(self, name: S) -> Self {
+        Error::In {
+            name: name.into(),
+            source: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Image { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Exr { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Parse { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Gltf { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Syntax {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Error::Malformed { path, message } => write!(f, "{}: {}", path.display(), message),
+            Error::UnsupportedFormat(path) => {
+                write!(f, "{}: unsupported image format", path.display())
+            }
+            Error::Undefined { kind, name } => write!(f, "undefined {} {}", kind, name),
+            Error::CyclicTexture(name) => write!(f, "texture {} refers to itself", name),
+            Error::InvalidTexture(reason) => write!(f, "invalid texture: {}", reason),
+            Error::InvalidMaterial(reason) => write!(f, "invalid material: {}", reason),
+            Error::DegenerateGeometry(reason) => write!(f, "degenerate geometry: {}", reason),
+            Error::InvalidCamera(reason) => write!(f, "invalid camera: {}", reason),
+            Error::InvalidAnimation(reason) => write!(f, "invalid animation: {}", reason),
+            Error::In { name, source } => write!(f, "{}: {}", name, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            Error::Exr { source, .. } => Some(source),
+            Error::Parse { source, .. } => Some(source),
+            Error::Gltf { source, .. } => Some(source),
+            Error::In { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::slice::ChunksMut;
+
+use exr::prelude::{AnyChannel, AnyChannels, FlatSamples, Image, SmallVec, Text, WritableImage};
+use image::{codecs::hdr::HdrEncoder, ImageFormat, Rgb, RgbImage};
+
+use crate::aov::Aov;
+use crate::color::{to_linear_rgb8, to_rgb8, write_colors, Color};
+use crate::error::{Error, Result};
+
+// Rendered image holding the linear, unclamped radiance of every pixel, along with
+// the auxiliary passes recorded during the same render.
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    aovs: Vec<(Aov, Framebuffer)>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::zeros(); width * height],
+            aovs: Vec::new(),
+        }
+    }
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "pixel count does not match size"
+        );
+        Self {
+            width,
+            height,
+            pixels,
+            aovs: Vec::new(),
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+    pub fn pixel(&self, i: usize, j: usize) -> Color {
+        self.pixels[j * self.width + i]
+    }
+    pub fn set_pixel(&mut self, i: usize, j: usize, color: Color) {
+        self.pixels[j * self.width + i] = color;
+    }
+    pub fn aovs(&self) -> &[(Aov, Framebuffer)] {
+        &self.aovs
+    }
+    pub fn aov(&self, aov: Aov) -> Option<&Framebuffer> {
+        self.aovs
+            .iter()
+            .find(|(a, _)| *a == aov)
+            .map(|(_, pass)| pass)
+    }
+    pub fn add_aov(&mut self, aov: Aov, pass: Framebuffer) {
+        assert!(
+            pass.width == self.width && pass.height == self.height,
+            "pass size does not match the image"
+        );
+        self.aovs.retain(|(a, _)| *a != aov);
+        self.aovs.push((aov, pass));
+    }
+    pub fn rows_mut(&mut self) -> ChunksMut<'_, Color> {
+        self.pixels.chunks_mut(self.width.max(1))
+    }
+
+    // Gamma corrected 8-bit RGB triplets, row by row from the top left pixel.
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&pixel| to_rgb8(pixel))
+            .collect()
+    }
+
+    // Plain text PPM (P3) encoder.
+    pub fn write_ppm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "P3\n{} {}\n255", self.width, self.height)?;
+        for &pixel_color in self.pixels.iter() {
+            write_colors(out, pixel_color)?;
+        }
+        Ok(())
+    }
+
+    // Binary PPM (P6) encoder.
+    pub fn write_p6<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write_p6(out, self.width, self.height, &self.to_rgb8())
+    }
+
+    // Portable float map encoder, storing the raw linear radiance without gamma.
+    pub fn write_pfm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        // A negative scale marks the samples as little endian.
+        write!(out, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        // PFM scanlines go from the bottom of the image to the top.
+        for row in self.pixels.chunks(self.width.max(1)).rev() {
+            for pixel in row {
+                for c in 0..3 {
+                    out.write_all(&(pixel[c] as f32).to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Radiance RGBE encoder, keeping the full dynamic range of the render.
+    pub fn write_hdr<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let data: Vec<Rgb<f32>> = self
+            .pixels
+            .iter()
+            .map(|p| Rgb([p.x() as f32, p.y() as f32, p.z() as f32]))
+            .collect();
+        HdrEncoder::new(out)
+            .encode(&data, self.width, self.height)
+            .map_err(io::Error::other)
+    }
+
+    // Write a single part OpenEXR file with the beauty pass in the R, G and B channels
+    // and every extra layer stored as `<layer>.<channel>` next to it.
+    pub fn save_exr<P: AsRef<Path>>(&self, path: P, layers: &[ExrLayer]) -> Result<()> {
+        let path = path.as_ref();
+        let mut channels = Vec::new();
+        for (c, name) in ["R", "G", "B"].iter().enumerate() {
+            channels.push(AnyChannel::new(*name, FlatSamples::F32(self.channel(c))));
+        }
+        for layer in layers {
+            if layer.image.width != self.width || layer.image.height != self.height {
+                return Err(Error::Malformed {
+                    path: path.into(),
+                    message: format!(
+                        "layer {} is {}x{}, the image {}x{}",
+                        layer.name, layer.image.width, layer.image.height, self.width, self.height
+                    ),
+                });
+            }
+            for (c, name) in layer.channels.iter().enumerate() {
+                let name = format!("{}.{}", layer.name, name);
+                channels.push(AnyChannel::new(
+                    Text::from(name.as_str()),
+                    FlatSamples::F32(layer.image.channel(c)),
+                ));
+            }
+        }
+
+        let channels = AnyChannels::sort(SmallVec::from_vec(channels));
+        Image::from_channels((self.width, self.height), channels)
+            .write()
+            .to_file(path)
+            .map_err(|source| Error::Exr {
+                path: path.into(),
+                source,
+            })
+    }
+
+    fn channel(&self, c: usize) -> Vec<f32> {
+        self.pixels.iter().map(|p| p[c] as f32).collect()
+    }
+
+    // Whether `save` knows an encoder for the extension of `path`.
+    pub fn is_supported<P: AsRef<Path>>(path: P) -> bool {
+        FORMATS.contains(&extension(path.as_ref()).as_str())
+    }
+
+    // Save the image, picking the encoder from the file extension.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        match extension(path).as_str() {
+            "png" => self.save_8bit(path, ImageFormat::Png, self.to_rgb8()),
+            "jpg" | "jpeg" => self.save_8bit(path, ImageFormat::Jpeg, self.to_rgb8()),
+            "ppm" => self.save_with(path, Self::write_p6),
+            "pfm" => self.save_with(path, Self::write_pfm),
+            "hdr" => self.save_with(path, Self::write_hdr),
+            "exr" => {
+                let layers: Vec<ExrLayer> = self
+                    .aovs
+                    .iter()
+                    .map(|(aov, pass)| ExrLayer {
+                        name: aov.name(),
+                        channels: aov.channels(),
+                        image: pass,
+                    })
+                    .collect();
+                self.save_exr(path, &layers)
+            }
+            _ => Err(Error::UnsupportedFormat(path.into())),
+        }
+    }
+
+    // Save every pass to its own file next to `path`, as `<stem>.<pass>.<ext>`.
+    // OpenEXR output already stores the passes as layers of the main file.
+    // Data passes are remapped for 8-bit formats, see `save_data`.
+    pub fn save_aovs<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if extension.eq_ignore_ascii_case("exr") {
+            return Ok(());
+        }
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+        for (aov, pass) in self.aovs.iter() {
+            let path = path.with_file_name(format!("{}.{}.{}", stem, aov.name(), extension));
+            match aov.is_data() {
+                true => pass.save_data(*aov, &path)?,
+                false => pass.save(&path)?,
+            }
+        }
+        Ok(())
+    }
+
+    // Save a data pass. 8-bit formats get its values mapped into [0, 1] and written
+    // without the gamma curve, the others keep them as they are.
+    fn save_data(&self, aov: Aov, path: &Path) -> Result<()> {
+        let format = match extension(path).as_str() {
+            "png" => ImageFormat::Png,
+            "jpg" | "jpeg" => ImageFormat::Jpeg,
+            "ppm" => ImageFormat::Pnm,
+            _ => return self.save(path),
+        };
+        let remapped = self.remap(aov);
+        let rgb: Vec<u8> = remapped
+            .pixels
+            .iter()
+            .flat_map(|&pixel| to_linear_rgb8(pixel))
+            .collect();
+        match format {
+            ImageFormat::Pnm => self.save_with(path, |image, out| {
+                write_p6(out, image.width, image.height, &rgb)
+            }),
+            format => self.save_8bit(path, format, rgb),
+        }
+    }
+
+    // Depths and object ids over the largest one, normals from [-1, 1], and
+    // positions over the box they span.
+    fn remap(&self, aov: Aov) -> Framebuffer {
+        let pixels = match aov {
+            Aov::Normal => self
+                .pixels
+                .iter()
+                .map(|&normal| (normal + Color::ones()) / 2.0)
+                .collect(),
+            Aov::Position => {
+                let (min, max) = self.pixels.iter().fold(
+                    (
+                        Color::ones() * f64::INFINITY,
+                        Color::ones() * -f64::INFINITY,
+                    ),
+                    |(min, max), p| {
+                        (
+                            Color::from(min.x().min(p.x()), min.y().min(p.y()), min.z().min(p.z())),
+                            Color::from(max.x().max(p.x()), max.y().max(p.y()), max.z().max(p.z())),
+                        )
+                    },
+                );
+                let scale = |c: usize, v: f64| match max[c] > min[c] {
+                    true => (v - min[c]) / (max[c] - min[c]),
+                    false => 0.0,
+                };
+                self.pixels
+                    .iter()
+                    .map(|p| Color::from(scale(0, p.x()), scale(1, p.y()), scale(2, p.z())))
+                    .collect()
+            }
+            Aov::Depth | Aov::ObjectId => {
+                let max = self.pixels.iter().fold(0.0, |max: f64, p| max.max(p.x()));
+                match max > 0.0 {
+                    true => self.pixels.iter().map(|&p| p / max).collect(),
+                    false => self.pixels.clone(),
+                }
+            }
+            Aov::Albedo => self.pixels.clone(),
+        };
+        Framebuffer::from_pixels(self.width, self.height, pixels)
+    }
+
+    fn save_8bit(&self, path: &Path, format: ImageFormat, rgb: Vec<u8>) -> Result<()> {
+        RgbImage::from_raw(self.width as u32, self.height as u32, rgb)
+            .expect("framebuffer size does not match its pixels")
+            .save_with_format(path, format)
+            .map_err(|source| Error::Image {
+                path: path.into(),
+                source,
+            })
+    }
+
+    fn save_with(
+        &self,
+        path: &Path,
+        encoder: impl Fn(&Self, &mut BufWriter<File>) -> io::Result<()>,
+    ) -> Result<()> {
+        File::create(path)
+            .and_then(|file| {
+                let mut out = BufWriter::new(file);
+                encoder(self, &mut out)?;
+                out.flush()
+            })
+            .map_err(|source| Error::Io {
+                path: path.into(),
+                source,
+            })
+    }
+}
+
+// Extensions of the formats `Framebuffer::save` can write.
+pub const FORMATS: [&str; 7] = ["png", "jpg", "jpeg", "ppm", "pfm", "hdr", "exr"];
+
+fn write_p6<W: Write>(out: &mut W, width: usize, height: usize, rgb: &[u8]) -> io::Result<()> {
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    out.write_all(rgb)
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+// Extra channels stored in an OpenEXR file alongside the beauty pass, `channels`
+// naming the x, y and z components of `image` to keep (depth only needs one).
+pub struct ExrLayer<'a> {
+    pub name: &'a str,
+    pub channels: &'a [&'a str],
+    pub image: &'a Framebuffer,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exr_layers_must_match_the_image() {
+        let image = Framebuffer::new(4, 3);
+        let depth = Framebuffer::new(3, 4);
+        let layer = ExrLayer {
+            name: "depth",
+            channels: &["Z"],
+            image: &depth,
+        };
+        let path = std::env::temp_dir().join("ray_tracer_rs_mismatched_layer.exr");
+        match image.save_exr(&path, &[layer]) {
+            Err(Error::Malformed { message, .. }) => {
+                assert_eq!(message, "layer depth is 3x4, the image 4x3")
+            }
+            other => panic!("expected a malformed layer, got {:?}", other.err()),
+        }
+        assert!(!path.exists());
+    }
+}
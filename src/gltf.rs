@@ -0,0 +1,357 @@
+use std::array;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ::gltf::camera::Projection;
+use ::gltf::mesh::Mode;
+use ::gltf::{buffer, image, Gltf, Mesh, Node, Primitive, Semantic};
+use base64::Engine;
+
+use crate::bvh::BvhOptions;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::error::{Error, Result};
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::mesh::TriangleMesh;
+use crate::texture::{ImageTexture, SolidColor, Texture, VertexColor};
+use crate::tlas::{Blas, Tlas};
+use crate::transform::Transform;
+use crate::vec3::{Point3, Vec3};
+
+// Read a glTF or GLB file, with its buffers and images from the same file, data URIs
+// or files next to it. Every mesh becomes a bottom-level structure of triangle meshes,
+// built once and instanced by each node using it with the transforms of the nodes
+// above, under one top-level structure. Meshes without triangles are left out. The
+// first camera met while walking the scene is returned along with the world.
+pub fn load<P: AsRef<Path>>(path: P, bvh: BvhOptions) -> Result<(HittableList, Option<Camera>)> {
+    let path = path.as_ref();
+    let Gltf { document, mut blob } = Gltf::open(path).map_err(|source| Error::Gltf {
+        path: path.into(),
+        source,
+    })?;
+
+    let mut importer = Importer {
+        path: path.into(),
+        dir: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        buffers: Vec::new(),
+        meshes: HashMap::new(),
+        materials: HashMap::new(),
+        textures: HashMap::new(),
+        instances: Vec::new(),
+        bvh,
+        camera: None,
+    };
+    for buffer in document.buffers() {
+        let data = importer.buffer(&buffer, &mut blob)?;
+        importer.buffers.push(data);
+    }
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next());
+    for node in scene.iter().flat_map(|scene| scene.nodes()) {
+        importer.node(&node, &Transform::identity())?;
+    }
+    let world = match importer.instances.is_empty() {
+        true => HittableList::new(),
+        false => HittableList::from(Arc::new(Tlas::from(importer.instances, bvh))),
+    };
+    Ok((world, importer.camera))
+}
+
+struct Importer {
+    path: PathBuf,
+    dir: PathBuf,
+    buffers: Vec<Vec<u8>>,
+    meshes: HashMap<usize, Option<Blas>>,
+    // Keyed by material and whether the primitive has vertex colors.
+    materials: HashMap<(Option<usize>, bool), Arc<dyn Material>>,
+    textures: HashMap<usize, Arc<dyn Texture>>,
+    instances: Vec<(Blas, Transform)>,
+    bvh: BvhOptions,
+    camera: Option<Camera>,
+}
+
+impl Importer {
+    fn malformed(&self, message: String) -> Error {
+        Error::Malformed {
+            path: self.path.clone(),
+            message,
+        }
+    }
+
+    fn node(&mut self, node: &Node, parent: &Transform) -> Result<()> {
+        // glTF matrices are column-major.
+        let columns = node.transform().matrix();
+        let local = array::from_fn(|r| array::from_fn(|c| columns[c][r] as f64));
+        let Some(local) = Transform::from_matrix(local) else {
+            return Ok(()); // Scaled to nothing, along with its children.
+        };
+        let world = *parent * local;
+
+        if let (None, Some(camera)) = (&self.camera, node.camera()) {
+            if let Projection::Perspective(perspective) = camera.projection() {
+                let mut cam = Camera::default();
+                cam.vfov = (perspective.yfov() as f64).to_degrees();
+                if let Some(aspect_ratio) = perspective.aspect_ratio() {
+                    cam.aspect_ratio = aspect_ratio as f64;
+                }
+                // glTF cameras look down their -Z axis with +Y up.
+                cam.lookfrom = world.point(Point3::zeros());
+                cam.lookat = cam.lookfrom + world.vector(Vec3::from(0.0, 0.0, -1.0));
+                cam.vup = world.vector(Vec3::from(0.0, 1.0, 0.0));
+                self.camera = Some(cam);
+            }
+        }
+
+        if let Some(mesh) = node.mesh() {
+            if let Some(mesh) = self.mesh(&mesh)? {
+                self.instances.push((mesh, world));
+            }
+        }
+        for child in node.children() {
+            self.node(&child, &world)?;
+        }
+        Ok(())
+    }
+
+    // The structure of a mesh, or nothing when none of its primitives has triangles,
+    // an empty one having no box to place.
+    fn mesh(&mut self, mesh: &Mesh) -> Result<Option<Blas>> {
+        if let Some(object) = self.meshes.get(&mesh.index()) {
+            return Ok(object.clone());
+        }
+        let mut primitives = HittableList::new();
+        for primitive in mesh.primitives() {
+            if let Some(mesh) = self.primitive(&primitive)? {
+                primitives.add(Arc::new(mesh));
+            }
+        }
+        let blas = match primitives.objects.len() {
+            0 => None,
+            1 => Some(Blas::from(primitives.objects.remove(0))),
+            _ => Some(Blas::from_list(&mut primitives, self.bvh)),
+        };
+        self.meshes.insert(mesh.index(), blas.clone());
+        Ok(blas)
+    }
+
+    fn primitive(&mut self, primitive: &Primitive) -> Result<Option<TriangleMesh>> {
+        let mode = primitive.mode();
+        if !matches!(
+            mode,
+            Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan
+        ) {
+            return Ok(None); // Points and lines have no surface to render.
+        }
+        let vertex_colors = primitive.get(&Semantic::Colors(0)).is_some();
+        let mat = self.material(primitive.material(), vertex_colors)?;
+
+        let reader = primitive.reader(|buffer| self.buffers.get(buffer.index()).map(Vec::as_slice));
+        let Some(positions) = reader.read_positions() else {
+            return Err(self.malformed("mesh primitive without positions".to_string()));
+        };
+        let positions: Vec<Point3> = positions
+            .map(|[x, y, z]| Point3::from(x as f64, y as f64, z as f64))
+            .collect();
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if let Some(i) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+            return Err(self.malformed(format!(
+                "vertex index {} is out of range for {} vertices",
+                i,
+                positions.len()
+            )));
+        }
+        let triangles: Vec<[u32; 3]> = match mode {
+            Mode::TriangleStrip => (0..indices.len().saturating_sub(2))
+                .map(|i| match i % 2 {
+                    0 => [indices[i], indices[i + 1], indices[i + 2]],
+                    _ => [indices[i + 1], indices[i], indices[i + 2]],
+                })
+                .collect(),
+            Mode::TriangleFan => (1..indices.len().saturating_sub(1))
+                .map(|i| [indices[0], indices[i], indices[i + 1]])
+                .collect(),
+            _ => indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+        };
+        if triangles.is_empty() {
+            return Ok(None);
+        }
+
+        let count = positions.len();
+        let normals: Option<Vec<Vec3>> = reader.read_normals().map(|normals| {
+            normals
+                .map(|[x, y, z]| Vec3::from(x as f64, y as f64, z as f64))
+                .collect()
+        });
+        // glTF puts the texture origin at the top left, the renderer at the bottom left.
+        let uvs: Option<Vec<(f64, f64)>> = reader.read_tex_coords(0).map(|uvs| {
+            uvs.into_f32()
+                .map(|[u, v]| (u as f64, 1.0 - v as f64))
+                .collect()
+        });
+        let colors: Option<Vec<Color>> = reader.read_colors(0).map(|colors| {
+            colors
+                .into_rgb_f32()
+                .map(|[r, g, b]| Color::from(r as f64, g as f64, b as f64))
+                .collect()
+        });
+
+        let mut mesh = TriangleMesh::from(positions, triangles, mat);
+        if let Some(normals) =
+            normals.filter(|n| n.len() == count && !n.iter().any(|n| n.near_zero()))
+        {
+            mesh = mesh.with_normals(normals);
+        }
+        if let Some(uvs) = uvs.filter(|uvs| uvs.len() == count) {
+            mesh = mesh.with_uvs(uvs);
+        }
+        if let Some(colors) = colors.filter(|colors| colors.len() == count) {
+            mesh = mesh.with_colors(colors);
+        }
+        Ok(Some(mesh))
+    }
+
+    // Metallic-roughness materials are matched to the closest material of the renderer:
+    // emissive ones become lights, transmissive ones glass, metallic ones metal with the
+    // roughness as fuzz, and the rest Lambertian, textured by the base color texture
+    // and tinted by the vertex colors of primitives that have them.
+    fn material(
+        &mut self,
+        material: ::gltf::Material,
+        vertex_colors: bool,
+    ) -> Result<Arc<dyn Material>> {
+        let key = (material.index(), vertex_colors);
+        if let Some(mat) = self.materials.get(&key) {
+            return Ok(mat.clone());
+        }
+        let name = material.name().map_or_else(
+            || format!("materials[{}]", material.index().unwrap_or_default()),
+            str::to_string,
+        );
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor().map(f64::from);
+        let base = Color::from(r, g, b);
+        let [r, g, b] = material.emissive_factor().map(f64::from);
+        let emissive = Color::from(r, g, b) * material.emissive_strength().unwrap_or(1.0) as f64;
+
+        let mat: Arc<dyn Material> = if emissive.x().max(emissive.y()).max(emissive.z()) > 0.0 {
+            Arc::new(DiffuseLight::from_color(emissive))
+        } else if material
+            .transmission()
+            .is_some_and(|t| t.transmission_factor() > 0.5)
+        {
+            let ior = material.ior().unwrap_or(1.5) as f64;
+            Arc::new(Dielectric::try_new(ior).map_err(|e| e.within(name))?)
+        } else if pbr.metallic_factor() >= 0.5 {
+            let fuzz = (pbr.roughness_factor() as f64).clamp(0.0, 1.0);
+            Arc::new(Metal::try_new(base, fuzz).map_err(|e| e.within(name))?)
+        } else {
+            let base: Arc<dyn Texture> = match pbr.base_color_texture() {
+                Some(info) => self
+                    .texture(info.texture().source())
+                    .map_err(|e| e.within(name))?,
+                None => Arc::new(SolidColor::from_color(base)),
+            };
+            match vertex_colors {
+                true => Arc::new(Lambertian::from_texture(Arc::new(VertexColor::tinting(
+                    base,
+                )))),
+                false => Arc::new(Lambertian::from_texture(base)),
+            }
+        };
+        self.materials.insert(key, mat.clone());
+        Ok(mat)
+    }
+
+    fn texture(&mut self, image: image::Image) -> Result<Arc<dyn Texture>> {
+        if let Some(texture) = self.textures.get(&image.index()) {
+            return Ok(texture.clone());
+        }
+        let data = match image.source() {
+            image::Source::View { view, .. } => {
+                let buffer = &self.buffers[view.buffer().index()];
+                match buffer.get(view.offset()..view.offset() + view.length()) {
+                    Some(data) => data.to_vec(),
+                    None => return Err(self.malformed("image view out of its buffer".to_string())),
+                }
+            }
+            image::Source::Uri { uri, .. } => self.uri(uri)?,
+        };
+        let decoded = ::image::load_from_memory(&data).map_err(|source| Error::Image {
+            path: self.path.clone(),
+            source,
+        })?;
+        let texture: Arc<dyn Texture> = Arc::new(ImageTexture::from_image(decoded.to_rgb8())?);
+        self.textures.insert(image.index(), texture.clone());
+        Ok(texture)
+    }
+
+    fn buffer(&self, buffer: &buffer::Buffer, blob: &mut Option<Vec<u8>>) -> Result<Vec<u8>> {
+        let data = match buffer.source() {
+            buffer::Source::Bin => blob
+                .take()
+                .ok_or_else(|| self.malformed("missing binary chunk".to_string()))?,
+            buffer::Source::Uri(uri) => self.uri(uri)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(self.malformed(format!(
+                "buffer {} holds {} bytes, {} expected",
+                buffer.index(),
+                data.len(),
+                buffer.length()
+            )));
+        }
+        Ok(data)
+    }
+
+    // Only data URIs and paths relative to the glTF file are read, nothing is fetched.
+    fn uri(&self, uri: &str) -> Result<Vec<u8>> {
+        if let Some(data) = uri.strip_prefix("data:") {
+            let Some((_, encoded)) = data.split_once(";base64,") else {
+                return Err(self.malformed("data URIs must be base64 encoded".to_string()));
+            };
+            return base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|e| self.malformed(format!("invalid data URI: {}", e)));
+        }
+        if uri.contains("://") {
+            return Err(self.malformed(format!("only local files can be read, not {}", uri)));
+        }
+        let path = self.dir.join(percent_decode(uri));
+        fs::read(&path).map_err(|source| Error::Io { path, source })
+    }
+}
+
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
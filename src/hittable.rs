@@ -1,5 +1,4 @@
-use std::os::unix::prelude::DirEntryExt;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::aabb::Aabb;
 use crate::color::Color;
@@ -7,17 +6,25 @@ use crate::interval::Interval;
 use crate::material::{Material, Metal};
 use crate::ray::Ray;
 use crate::rtweekend::{degrees2radians, INF};
+use crate::transform::{AnimatedTransform, Transform};
 use crate::vec3::{Point3, Vec3};
 
+thread_local! {
+    // The material of records not yet hit, shared so that making one allocates nothing.
+    static NO_MATERIAL: Arc<dyn Material> = Arc::new(Metal::new(Color::zeros(), 0.0));
+}
+
 // #[derive(Debug, Clone, Copy)]
 pub struct HitRecord {
     pub p: Point3,
     pub normal: Vec3,
-    pub mat: Rc<dyn Material>,
+    pub mat: Arc<dyn Material>,
     pub t: f64,
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
+    pub object_id: u32, // Index + 1 of the outermost list entry hit, 0 when untagged
+    pub color: Option<Color>, // Interpolated vertex color of meshes that have one
 }
 
 impl HitRecord {
@@ -25,12 +32,13 @@ impl HitRecord {
         Self {
             p: Point3::zeros(),
             normal: Vec3::zeros(),
-            mat: Rc::new(Metal::new(Color::zeros(), 0.0)),
+            mat: NO_MATERIAL.with(Arc::clone),
             t: 0.0,
             u: 0.0,
             v: 0.0,
             front_face: false,
-            // color: Color::zeros(),
+            object_id: 0,
+            color: None,
         }
     }
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: &Vec3) {
@@ -42,20 +50,37 @@ impl HitRecord {
     }
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
 
+    // Whether `hit` would find anything within `ray_t`, for shadow rays and ambient
+    // occlusion. Stops at the first hit met instead of the closest, and fills no
+    // record.
+    fn occluded(&self, r: &Ray, ray_t: Interval) -> bool;
+
     fn bounding_box(&self) -> Aabb;
+
+    // Narrow the boxes kept by the object down to what it covers while `time` lasts,
+    // before rendering rays of those times only. Objects that do not move keep theirs.
+    fn refit(&mut self, _time: Interval) {}
+}
+
+// Refit an object held by a parent. Objects shared with other parents cannot be
+// changed from here and are left as they are.
+pub fn refit_object(object: &mut Arc<dyn Hittable>, time: Interval) {
+    if let Some(object) = Arc::get_mut(object) {
+        object.refit(time);
+    }
 }
 
 pub struct Translate {
-    object: Rc<dyn Hittable>,
+    object: Arc<dyn Hittable>,
     offset: Vec3,
     bbox: Aabb,
 }
 
 impl Translate {
-    pub fn from(object: Rc<dyn Hittable>, displacement: Vec3) -> Self {
+    pub fn from(object: Arc<dyn Hittable>, displacement: Vec3) -> Self {
         Self {
             object: object.clone(),
             offset: displacement,
@@ -68,6 +93,10 @@ impl Hittable for Translate {
     fn bounding_box(&self) -> Aabb {
         self.bbox
     }
+    fn refit(&mut self, time: Interval) {
+        refit_object(&mut self.object, time);
+        self.bbox = self.object.bounding_box() + self.offset;
+    }
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
         // Move the ray backwards by the offset
         let offset_r = Ray::from(r.origin() - self.offset, r.direction(), r.time());
@@ -82,21 +111,37 @@ impl Hittable for Translate {
 
         true
     }
+    fn occluded(&self, r: &Ray, ray_t: Interval) -> bool {
+        let offset_r = Ray::from(r.origin() - self.offset, r.direction(), r.time());
+        self.object.occluded(&offset_r, ray_t)
+    }
 }
 
 pub struct RotateY {
-    object: Rc<dyn Hittable>,
+    object: Arc<dyn Hittable>,
     sin_theta: f64,
     cos_theta: f64,
     bbox: Aabb,
 }
 
 impl RotateY {
-    pub fn from(p: Rc<dyn Hittable>, angle: f64) -> Self {
+    pub fn from(p: Arc<dyn Hittable>, angle: f64) -> Self {
         let radians = degrees2radians(angle);
         let sin_theta = radians.sin();
         let cos_theta = radians.cos();
-        let bbox = p.bounding_box();
+        let mut rotate = Self {
+            object: p,
+            sin_theta,
+            cos_theta,
+            bbox: Aabb::new(),
+        };
+        rotate.bbox = rotate.rotated_bbox();
+        rotate
+    }
+
+    fn rotated_bbox(&self) -> Aabb {
+        let bbox = self.object.bounding_box();
+        let (sin_theta, cos_theta) = (self.sin_theta, self.cos_theta);
 
         let mut min = Point3::from(INF, INF, INF);
         let mut max = Point3::from(-INF, -INF, -INF);
@@ -109,28 +154,24 @@ impl RotateY {
                     let z = bbox.z().max() * k as f64 + (1.0 - k as f64) * bbox.z().min();
 
                     let newx = cos_theta * x + sin_theta * z;
-                    let newz = -1.0 * sin_theta * x + cos_theta * z;
+                    let newz = -sin_theta * x + cos_theta * z;
 
                     let tester = Vec3::from(newx, y, newz);
 
                     for c in 0..3 {
-                        min[c] = (min[c] as f64).min(tester[c]);
-                        max[c] = (max[c] as f64).max(tester[c]);
+                        min[c] = f64::min(min[c], tester[c]);
+                        max[c] = f64::max(max[c], tester[c]);
                     }
                 }
             }
         }
-        Self {
-            object: p,
-            sin_theta,
-            cos_theta,
-            bbox: Aabb::from_points(min, max),
-        }
+        Aabb::from_points(min, max)
     }
 }
 
-impl Hittable for RotateY {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+impl RotateY {
+    // The ray in the space of the object.
+    fn rotated_ray(&self, r: &Ray) -> Ray {
         let mut origin = r.origin();
         let mut direction = r.direction();
 
@@ -140,24 +181,148 @@ impl Hittable for RotateY {
         direction[0] = self.cos_theta * r.direction()[0] - self.sin_theta * r.direction()[2];
         direction[2] = self.sin_theta * r.direction()[0] + self.cos_theta * r.direction()[2];
 
-        let rotated_r = Ray::from(origin, direction, r.time());
+        Ray::from(origin, direction, r.time())
+    }
+}
+
+impl Hittable for RotateY {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let rotated_r = self.rotated_ray(r);
 
         if !self.object.hit(&rotated_r, ray_t, rec) {
             return false;
         }
         let mut p = rec.p;
         p[0] = self.cos_theta * rec.p[0] + self.sin_theta * rec.p[2];
-        p[2] = -1.0 * self.sin_theta * rec.p[0] + self.cos_theta * rec.p[2];
+        p[2] = -self.sin_theta * rec.p[0] + self.cos_theta * rec.p[2];
 
         let mut normal = rec.normal;
         normal[0] = self.cos_theta * rec.normal[0] + self.sin_theta * rec.normal[2];
-        normal[2] = -1.0 * self.sin_theta * rec.normal[0] + self.cos_theta * rec.normal[2];
+        normal[2] = -self.sin_theta * rec.normal[0] + self.cos_theta * rec.normal[2];
 
         rec.p = p;
         rec.normal = normal;
         true
     }
+    fn occluded(&self, r: &Ray, ray_t: Interval) -> bool {
+        self.object.occluded(&self.rotated_ray(r), ray_t)
+    }
     fn bounding_box(&self) -> Aabb {
         self.bbox
     }
+    fn refit(&mut self, time: Interval) {
+        refit_object(&mut self.object, time);
+        self.bbox = self.rotated_bbox();
+    }
+}
+
+// Places an object with any affine transform. Rays are taken into the space of the
+// object, where it is hit as usual, and the hit is brought back out.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    bbox: Aabb,
+}
+
+impl Instance {
+    pub fn from(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        Self {
+            bbox: transform.bbox(object.bounding_box()),
+            object,
+            transform,
+        }
+    }
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        hit_transformed(&*self.object, &self.transform, r, ray_t, rec)
+    }
+    fn occluded(&self, r: &Ray, ray_t: Interval) -> bool {
+        self.object.occluded(&local_ray(&self.transform, r), ray_t)
+    }
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+    fn refit(&mut self, time: Interval) {
+        refit_object(&mut self.object, time);
+        self.bbox = self.transform.bbox(self.object.bounding_box());
+    }
+}
+
+// An object moving through keyframes during the shutter, posed at the time of each ray.
+pub struct AnimatedInstance {
+    object: Arc<dyn Hittable>,
+    motion: AnimatedTransform,
+    bbox: Aabb,
+}
+
+impl AnimatedInstance {
+    pub fn from(object: Arc<dyn Hittable>, motion: AnimatedTransform) -> Self {
+        Self {
+            bbox: motion.bbox(object.bounding_box()),
+            object,
+            motion,
+        }
+    }
+    pub fn motion(&self) -> &AnimatedTransform {
+        &self.motion
+    }
+}
+
+impl Hittable for AnimatedInstance {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let transform = self.motion.at(r.time());
+        hit_transformed(&*self.object, &transform, r, ray_t, rec)
+    }
+    fn occluded(&self, r: &Ray, ray_t: Interval) -> bool {
+        let transform = self.motion.at(r.time());
+        self.object.occluded(&local_ray(&transform, r), ray_t)
+    }
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+    fn refit(&mut self, time: Interval) {
+        refit_object(&mut self.object, time);
+        self.bbox = self.motion.bbox_during(self.object.bounding_box(), time);
+    }
+}
+
+fn hit_transformed(
+    object: &dyn Hittable,
+    transform: &Transform,
+    r: &Ray,
+    ray_t: Interval,
+    rec: &mut HitRecord,
+) -> bool {
+    if !object.hit(&local_ray(transform, r), ray_t, rec) {
+        return false;
+    }
+
+    rec.p = transform.point(rec.p);
+    // The inverse transpose keeps the sign of the normal against the ray, so
+    // `front_face` still holds. Volumes report no normal and keep none.
+    if !rec.normal.near_zero() {
+        rec.normal = transform.normal(rec.normal).unit_vector();
+    }
+    true
+}
+
+// The ray in the space of an object placed by `transform`. The direction is not
+// normalized, so t is the same in both spaces.
+fn local_ray(transform: &Transform, r: &Ray) -> Ray {
+    Ray::from(
+        transform.inverse_point(r.origin()),
+        transform.inverse_vector(r.direction()),
+        r.time(),
+    )
+}
+
+impl Default for HitRecord {
+    fn default() -> Self {
+        Self::new()
+    }
 }
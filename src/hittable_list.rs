@@ -1,11 +1,11 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::aabb::Aabb;
-use crate::hittable::{HitRecord, Hittable};
+use crate::aabb::{self, Aabb};
+use crate::hittable::{refit_object, HitRecord, Hittable};
 use crate::interval::Interval;
 
 pub struct HittableList {
-    pub objects: Vec<Rc<dyn Hittable>>,
+    pub objects: Vec<Arc<dyn Hittable>>,
     bbox: Aabb,
 }
 
@@ -13,24 +13,25 @@ impl HittableList {
     pub fn new() -> Self {
         Self {
             objects: vec![],
-            bbox: Aabb::new(),
+            bbox: aabb::EMPTY,
         }
     }
 
-    pub fn from(object: Rc<dyn Hittable>) -> Self {
+    pub fn from(object: Arc<dyn Hittable>) -> Self {
         Self {
+            bbox: object.bounding_box(),
             objects: vec![object],
-            bbox: Aabb::new(),
         }
     }
 
-    pub fn add(&mut self, object: Rc<dyn Hittable>) {
+    pub fn add(&mut self, object: Arc<dyn Hittable>) {
         self.bbox = Aabb::from_bbox(self.bbox, object.bounding_box());
         self.objects.push(object);
     }
 
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.bbox = aabb::EMPTY;
     }
 }
 
@@ -39,7 +40,10 @@ impl Hittable for HittableList {
         let mut hit_anything = false;
         let mut closest_so_far = ray_t.max();
 
-        for object in self.objects.iter() {
+        // A list wrapping a single object only forwards the ids assigned below it.
+        let tag = self.objects.len() > 1;
+
+        for (i, object) in self.objects.iter().enumerate() {
             let mut temp_rec = HitRecord::new();
             if object.hit(
                 r,
@@ -48,13 +52,33 @@ impl Hittable for HittableList {
             ) {
                 hit_anything = true;
                 closest_so_far = temp_rec.t;
+                if tag {
+                    temp_rec.object_id = i as u32 + 1;
+                }
                 *rec = temp_rec;
             }
         }
         hit_anything
     }
 
+    fn occluded(&self, r: &crate::ray::Ray, ray_t: Interval) -> bool {
+        self.objects.iter().any(|object| object.occluded(r, ray_t))
+    }
+
     fn bounding_box(&self) -> crate::aabb::Aabb {
         self.bbox
     }
+    fn refit(&mut self, time: Interval) {
+        self.bbox = aabb::EMPTY;
+        for object in self.objects.iter_mut() {
+            refit_object(object, time);
+            self.bbox = Aabb::from_bbox(self.bbox, object.bounding_box());
+        }
+    }
+}
+
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -34,7 +34,7 @@ impl Interval {
     }
     pub fn clamp(self, x: f64) -> f64 {
         if x < self.min {
-            return self.max;
+            return self.min;
         }
         if x > self.max {
             return self.max;
@@ -60,7 +60,7 @@ impl Interval {
         let padding = delta / 2.0;
         Self {
             min: self.min - padding,
-            max: self.max - padding,
+            max: self.max + padding,
         }
     }
 }
@@ -73,4 +73,24 @@ impl ops::Add<f64> for Interval {
 }
 
 pub const UNIVERSE: Interval = Interval::from(-INF, INF);
-const EMPTY: Interval = Interval::from(INF, -INF);
+pub const EMPTY: Interval = Interval::from(INF, -INF);
+
+impl Default for Interval {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_pads_both_ends() {
+        let padded = Interval::from(1.0, 2.0).expand(0.5);
+        assert_eq!((padded.min(), padded.max()), (0.75, 2.25));
+        // Flat boxes, such as those of quads, are padded around their plane.
+        let flat = Interval::from(3.0, 3.0).expand(0.0001);
+        assert!(flat.surrounds(3.0));
+    }
+}
@@ -0,0 +1,49 @@
+pub mod aabb;
+pub mod animation;
+pub mod aov;
+pub mod bvh;
+pub mod camera;
+pub mod color;
+pub mod constant_medium;
+pub mod error;
+pub mod framebuffer;
+pub mod gltf;
+pub mod hittable;
+pub mod hittable_list;
+pub mod interval;
+pub mod material;
+pub mod mesh;
+pub mod obj;
+pub mod perlin;
+pub mod ply;
+pub mod quad;
+pub mod ray;
+pub mod rtweekend;
+pub mod scene_file;
+pub mod scenes;
+pub mod sphere;
+pub mod stl;
+pub mod texture;
+pub mod tlas;
+pub mod transform;
+pub mod triangle;
+pub mod vec3;
+
+pub use animation::{Animation, CameraKey};
+pub use bvh::{BvhNode, BvhOptions, BvhSplit, BvhWidth};
+pub use camera::{Camera, CameraKeyframe};
+pub use color::Color;
+pub use error::{Error, Result};
+pub use framebuffer::Framebuffer;
+pub use hittable::{AnimatedInstance, HitRecord, Hittable, Instance, RotateY, Translate};
+pub use hittable_list::HittableList;
+pub use material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+pub use mesh::TriangleMesh;
+pub use quad::{box_volume, Quad};
+pub use ray::Ray;
+pub use sphere::Sphere;
+pub use texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture, VertexColor};
+pub use tlas::{Blas, Tlas};
+pub use transform::{AnimatedTransform, Keyframe, Transform};
+pub use triangle::Triangle;
+pub use vec3::{Point3, Vec3};
@@ -1,10 +1,11 @@
+use crate::error::{Error, Result};
 use crate::rtweekend::random_double;
 use crate::texture::{SolidColor, Texture};
 use crate::vec3::Point3;
 use crate::{color::Color, hittable::HitRecord, ray::Ray, vec3::Vec3};
-use std::rc::Rc;
+use std::sync::Arc;
 
-pub trait Material {
+pub trait Material: Send + Sync {
     fn scatter(
         &self,
         r_in: &Ray,
@@ -16,19 +17,24 @@ pub trait Material {
     fn emitted(&self, _: f64, _: f64, _: &Point3) -> Color {
         Color::zeros()
     }
+
+    // Surface color at the hit, as recorded in the albedo pass.
+    fn albedo(&self, _: &HitRecord) -> Color {
+        Color::zeros()
+    }
 }
 
 pub struct Lambertian {
-    albedo: Rc<dyn Texture>,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn from_color(albedo: Color) -> Self {
         Self {
-            albedo: Rc::new(SolidColor::from_color(albedo)),
+            albedo: Arc::new(SolidColor::from_color(albedo)),
         }
     }
-    pub fn from_texture(a: Rc<dyn Texture>) -> Self {
+    pub fn from_texture(a: Arc<dyn Texture>) -> Self {
         Self { albedo: a }
     }
 }
@@ -46,9 +52,13 @@ impl Material for Lambertian {
             scatter_direction = rec.normal;
         }
         *scattered = Ray::from(rec.p, scatter_direction, r_in.time());
-        *attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
+        *attenuation = self.albedo.value_at(rec);
         true
     }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value_at(rec)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -58,6 +68,8 @@ pub struct Metal {
 }
 
 impl Metal {
+    // A fuzz of 1 or more is clamped to 1. `try_new` rejects a fuzz outside [0, 1]
+    // instead, for input that may have one.
     pub fn new(albedo: Color, fuzz: f64) -> Self {
         Self {
             albedo,
@@ -67,6 +79,15 @@ impl Metal {
             },
         }
     }
+    pub fn try_new(albedo: Color, fuzz: f64) -> Result<Self> {
+        if !(0.0..=1.0).contains(&fuzz) {
+            return Err(Error::InvalidMaterial(format!(
+                "metal fuzz must be within [0, 1], got {}",
+                fuzz
+            )));
+        }
+        Ok(Self { albedo, fuzz })
+    }
 }
 
 impl Material for Metal {
@@ -86,6 +107,10 @@ impl Material for Metal {
         *attenuation = self.albedo;
         scattered.direction().dot(rec.normal) > 0.0
     }
+
+    fn albedo(&self, _: &HitRecord) -> Color {
+        self.albedo
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -94,9 +119,19 @@ pub struct Dielectric {
 }
 
 impl Dielectric {
+    // `try_new` rejects an index that is not positive, for input that may have one.
     pub fn new(ir: f64) -> Self {
         Self { ir }
     }
+    pub fn try_new(ir: f64) -> Result<Self> {
+        if !(ir > 0.0 && ir.is_finite()) {
+            return Err(Error::InvalidMaterial(format!(
+                "dielectric index of refraction must be positive, got {}",
+                ir
+            )));
+        }
+        Ok(Self { ir })
+    }
 }
 
 fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
@@ -135,19 +170,23 @@ impl Material for Dielectric {
 
         true
     }
+
+    fn albedo(&self, _: &HitRecord) -> Color {
+        Color::ones()
+    }
 }
 
 pub struct DiffuseLight {
-    emit: Rc<dyn Texture>,
+    emit: Arc<dyn Texture>,
 }
 
 impl DiffuseLight {
-    pub fn from_material(emit: Rc<dyn Texture>) -> Self {
+    pub fn from_material(emit: Arc<dyn Texture>) -> Self {
         Self { emit }
     }
     pub fn from_color(color: Color) -> Self {
         Self {
-            emit: Rc::new(SolidColor::from_color(color)),
+            emit: Arc::new(SolidColor::from_color(color)),
         }
     }
     // pub fn emitted(self, u: f64, v: f64, p: &Point3) -> Color {
@@ -163,19 +202,23 @@ impl Material for DiffuseLight {
     fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
         self.emit.value(u, v, p)
     }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.emit.value_at(rec)
+    }
 }
 
 pub struct Isotropic {
-    albedo: Rc<dyn Texture>,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Isotropic {
     pub fn from_color(color: Color) -> Self {
         Self {
-            albedo: Rc::new(SolidColor::from_color(color)),
+            albedo: Arc::new(SolidColor::from_color(color)),
         }
     }
-    pub fn from_texture(texture: Rc<dyn Texture>) -> Self {
+    pub fn from_texture(texture: Arc<dyn Texture>) -> Self {
         Self { albedo: texture }
     }
 }
@@ -189,8 +232,24 @@ impl Material for Isotropic {
         scattered: &mut Ray,
     ) -> bool {
         *scattered = Ray::from(rec.p, Vec3::random_unit_vector(), r_in.time());
-        *attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
+        *attenuation = self.albedo.value_at(rec);
 
         true
     }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.albedo.value_at(rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metal_new_clamps_where_try_new_rejects() {
+        assert_eq!(Metal::new(Color::ones(), 1.5).fuzz, 1.0);
+        assert!(Metal::try_new(Color::ones(), 1.5).is_err());
+        assert_eq!(Metal::try_new(Color::ones(), 0.5).unwrap().fuzz, 0.5);
+    }
 }
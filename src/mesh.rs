@@ -0,0 +1,243 @@
+use crate::{
+    aabb::Aabb,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    triangle::{intersect, set_hit_record},
+    vec3::{Point3, Vec3},
+};
+use std::sync::Arc;
+
+const LEAF_SIZE: usize = 4;
+const STACK_SIZE: usize = 64;
+
+// An indexed triangle mesh with a single material. The vertex buffers are shared by
+// every triangle and searched through a BVH of its own, so a large model is one
+// object of the scene and can be instanced by wrapping the same `Arc` several times.
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>, // Per-vertex shading normals, empty to use the face normals
+    uvs: Vec<(f64, f64)>, // Per-vertex texture coordinates, may be empty
+    colors: Vec<Color>, // Per-vertex colors, read by the `VertexColor` texture
+    indices: Vec<[u32; 3]>, // Ordered so that every leaf covers a contiguous range
+    mat: Arc<dyn Material>,
+    nodes: Vec<MeshNode>,
+}
+
+// Leaves hold `count` triangles starting at `first`, interior nodes have a count of 0
+// and their two children stored next to each other at `first`.
+#[derive(Clone, Copy)]
+struct MeshNode {
+    bbox: Aabb,
+    first: u32,
+    count: u32,
+}
+
+struct Primitive {
+    triangle: [u32; 3],
+    bbox: Aabb,
+    centroid: Point3,
+}
+
+impl TriangleMesh {
+    pub fn from(positions: Vec<Point3>, indices: Vec<[u32; 3]>, mat: Arc<dyn Material>) -> Self {
+        assert!(
+            indices
+                .iter()
+                .flatten()
+                .all(|&i| (i as usize) < positions.len()),
+            "triangle index out of range"
+        );
+        let mut primitives: Vec<Primitive> = indices
+            .iter()
+            .map(|&triangle| {
+                let [a, b, c] = triangle.map(|i| positions[i as usize]);
+                Primitive {
+                    triangle,
+                    bbox: Aabb::from_bbox(Aabb::from_points(a, b), Aabb::from_points(a, c)).pad(),
+                    centroid: (a + b + c) / 3.0,
+                }
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        if !primitives.is_empty() {
+            nodes.push(MeshNode {
+                bbox: Aabb::new(),
+                first: 0,
+                count: 0,
+            });
+            Self::build(&mut nodes, 0, &mut primitives, 0);
+        }
+
+        Self {
+            positions,
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            colors: Vec::new(),
+            indices: primitives.iter().map(|p| p.triangle).collect(),
+            mat,
+            nodes,
+        }
+    }
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Self {
+        assert_eq!(normals.len(), self.positions.len(), "one normal per vertex");
+        self.normals = normals.into_iter().map(Vec3::unit_vector).collect();
+        self
+    }
+    pub fn with_uvs(mut self, uvs: Vec<(f64, f64)>) -> Self {
+        assert_eq!(uvs.len(), self.positions.len(), "one uv per vertex");
+        self.uvs = uvs;
+        self
+    }
+    pub fn with_colors(mut self, colors: Vec<Color>) -> Self {
+        assert_eq!(colors.len(), self.positions.len(), "one color per vertex");
+        self.colors = colors;
+        self
+    }
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    fn build(nodes: &mut Vec<MeshNode>, node: usize, primitives: &mut [Primitive], first: usize) {
+        let bbox = primitives[1..]
+            .iter()
+            .fold(primitives[0].bbox, |bbox, p| Aabb::from_bbox(bbox, p.bbox));
+        nodes[node] = MeshNode {
+            bbox,
+            first: first as u32,
+            count: primitives.len() as u32,
+        };
+        if primitives.len() <= LEAF_SIZE {
+            return;
+        }
+
+        // Split at the median centroid along the axis where the centroids spread most.
+        let centroids = primitives[1..].iter().fold(
+            Aabb::from_points(primitives[0].centroid, primitives[0].centroid),
+            |bbox, p| Aabb::from_bbox(bbox, Aabb::from_points(p.centroid, p.centroid)),
+        );
+        let axis = (0..3)
+            .max_by(|&a, &b| {
+                let (a, b) = (centroids.axis(a).size(), centroids.axis(b).size());
+                a.total_cmp(&b)
+            })
+            .unwrap_or(0);
+        if centroids.axis(axis).size() <= 0.0 {
+            return; // Every centroid is in the same place, keep them in one leaf.
+        }
+        let mid = primitives.len() / 2;
+        primitives
+            .select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+
+        let left = nodes.len();
+        nodes[node].first = left as u32;
+        nodes[node].count = 0;
+        nodes.extend([nodes[node]; 2]);
+        let (left_primitives, right_primitives) = primitives.split_at_mut(mid);
+        Self::build(nodes, left, left_primitives, first);
+        Self::build(nodes, left + 1, right_primitives, first + mid);
+    }
+
+    fn vertices(&self, triangle: [u32; 3]) -> [Point3; 3] {
+        triangle.map(|i| self.positions[i as usize])
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        // Walk the tree with a fixed stack, only keeping the closest triangle so the
+        // hit record is filled once.
+        let mut closest = None;
+        let mut t_max = ray_t.max();
+        let mut stack = [0u32; STACK_SIZE];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let node = self.nodes[stack[len] as usize];
+            if !node.bbox.hit(r, &mut Interval::from(ray_t.min(), t_max)) {
+                continue;
+            }
+            if node.count == 0 {
+                stack[len] = node.first + 1;
+                stack[len + 1] = node.first;
+                len += 2;
+                continue;
+            }
+            let first = node.first as usize;
+            for &triangle in &self.indices[first..first + node.count as usize] {
+                let interval = Interval::from(ray_t.min(), t_max);
+                if let Some(hit) = intersect(r, interval, self.vertices(triangle)) {
+                    t_max = hit.0;
+                    closest = Some((triangle, hit));
+                }
+            }
+        }
+
+        let Some((triangle, hit)) = closest else {
+            return false;
+        };
+        let [a, b, c] = self.vertices(triangle);
+        let normals = match self.normals.is_empty() {
+            true => None,
+            false => Some(triangle.map(|i| self.normals[i as usize])),
+        };
+        let uvs = match self.uvs.is_empty() {
+            true => [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            false => triangle.map(|i| self.uvs[i as usize]),
+        };
+        let normal = (b - a).cross(c - a).unit_vector();
+        set_hit_record(r, rec, hit, normal, normals, uvs, &self.mat);
+        if !self.colors.is_empty() {
+            let (_, beta, gamma) = hit;
+            let [ca, cb, cc] = triangle.map(|i| self.colors[i as usize]);
+            rec.color = Some(ca * (1.0 - beta - gamma) + cb * beta + cc * gamma);
+        }
+        true
+    }
+    fn occluded(&self, r: &Ray, ray_t: Interval) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        // The same walk, returning at the first triangle hit.
+        let mut stack = [0u32; STACK_SIZE];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let node = self.nodes[stack[len] as usize];
+            if !node.bbox.hit(r, &mut ray_t.clone()) {
+                continue;
+            }
+            if node.count == 0 {
+                stack[len] = node.first + 1;
+                stack[len + 1] = node.first;
+                len += 2;
+                continue;
+            }
+            let first = node.first as usize;
+            if self.indices[first..first + node.count as usize]
+                .iter()
+                .any(|&triangle| intersect(r, ray_t, self.vertices(triangle)).is_some())
+            {
+                return true;
+            }
+        }
+        false
+    }
+    fn bounding_box(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(root) => root.bbox,
+            None => Aabb::from_points(Point3::zeros(), Point3::zeros()).pad(),
+        }
+    }
+}
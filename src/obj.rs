@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::error::{Error, Result};
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::mesh::TriangleMesh;
+use crate::texture::ImageTexture;
+use crate::vec3::{Point3, Vec3};
+
+// Position, texture coordinate and normal indices of a face corner, all from 0.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+// Faces sharing a material, which become one mesh.
+struct Group {
+    material: Option<String>,
+    triangles: Vec<[Corner; 3]>,
+}
+
+// Read a Wavefront OBJ file and the MTL libraries it refers to. Every `usemtl`
+// group becomes one `TriangleMesh`, faces before any `usemtl` use `default`.
+pub fn load<P: AsRef<Path>>(path: P, default: Arc<dyn Material>) -> Result<HittableList> {
+    let path = path.as_ref();
+    let text = read(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let syntax = |line: usize, message: String| Error::Syntax {
+        path: path.into(),
+        line,
+        message,
+    };
+
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut groups = vec![Group {
+        material: None,
+        triangles: Vec::new(),
+    }];
+    let mut current = 0;
+    let mut library = HashMap::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let n = n + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        match keyword {
+            "v" => {
+                let [x, y, z, ..] = floats(tokens, 3).map_err(|e| syntax(n, e))?[..] else {
+                    unreachable!()
+                };
+                positions.push(Point3::from(x, y, z));
+            }
+            "vt" => {
+                let uv = floats(tokens, 1).map_err(|e| syntax(n, e))?;
+                uvs.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+            }
+            "vn" => {
+                let [x, y, z, ..] = floats(tokens, 3).map_err(|e| syntax(n, e))?[..] else {
+                    unreachable!()
+                };
+                normals.push(Vec3::from(x, y, z));
+            }
+            "f" => {
+                let counts = (positions.len(), uvs.len(), normals.len());
+                let corners = tokens
+                    .map(|token| corner(token, counts))
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|e| syntax(n, e))?;
+                if corners.len() < 3 {
+                    return Err(syntax(n, "a face needs at least 3 vertices".to_string()));
+                }
+                let points: Vec<Point3> = corners.iter().map(|c| positions[c.0]).collect();
+                for [a, b, c] in triangulate(&points) {
+                    groups[current]
+                        .triangles
+                        .push([corners[a], corners[b], corners[c]]);
+                }
+            }
+            "usemtl" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                current = match groups
+                    .iter()
+                    .position(|g| g.material.as_deref() == Some(&name))
+                {
+                    Some(i) => i,
+                    None => {
+                        groups.push(Group {
+                            material: Some(name),
+                            triangles: Vec::new(),
+                        });
+                        groups.len() - 1
+                    }
+                };
+            }
+            "mtllib" => {
+                for file in tokens {
+                    library.extend(load_mtl(&dir.join(file))?);
+                }
+            }
+            // Object and smoothing groups do not change how faces are rendered.
+            _ => {}
+        }
+    }
+
+    let mut materials: HashMap<&str, Arc<dyn Material>> = HashMap::new();
+    let mut list = HittableList::new();
+    for group in groups.iter().filter(|g| !g.triangles.is_empty()) {
+        let mat = match group.material.as_deref() {
+            None => default.clone(),
+            Some(name) => match materials.get(name) {
+                Some(mat) => mat.clone(),
+                None => {
+                    let Some(desc) = library.get(name) else {
+                        return Err(Error::Undefined {
+                            kind: "material",
+                            name: name.to_string(),
+                        });
+                    };
+                    let mat = desc.material().map_err(|e| e.within(name))?;
+                    materials.insert(name, mat.clone());
+                    mat
+                }
+            },
+        };
+        list.add(Arc::new(mesh(group, &positions, &uvs, &normals, mat)));
+    }
+    Ok(list)
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.into(),
+        source,
+    })
+}
+
+fn floats<'a, I: Iterator<Item = &'a str>>(
+    tokens: I,
+    min: usize,
+) -> std::result::Result<Vec<f64>, String> {
+    let values = tokens
+        .map(|t| t.parse().map_err(|_| format!("invalid number: {}", t)))
+        .collect::<std::result::Result<Vec<f64>, _>>()?;
+    match values.len() < min {
+        true => Err(format!("expected {} numbers, got {}", min, values.len())),
+        false => Ok(values),
+    }
+}
+
+// Parse `v`, `v/vt`, `v//vn` or `v/vt/vn`, where negative indices count back from
+// the last element defined so far.
+fn corner(
+    token: &str,
+    (positions, uvs, normals): (usize, usize, usize),
+) -> std::result::Result<Corner, String> {
+    let index = |field: Option<&str>, len: usize| -> std::result::Result<Option<usize>, String> {
+        let Some(field) = field.filter(|f| !f.is_empty()) else {
+            return Ok(None);
+        };
+        let i: i64 = field
+            .parse()
+            .map_err(|_| format!("invalid index: {}", field))?;
+        let resolved = match i {
+            i if i > 0 => i - 1,
+            i if i < 0 => len as i64 + i,
+            _ => -1,
+        };
+        match (0..len as i64).contains(&resolved) {
+            true => Ok(Some(resolved as usize)),
+            false => Err(format!("index {} is out of range", i)),
+        }
+    };
+    let mut fields = token.split('/');
+    let position = index(fields.next(), positions)?.ok_or("missing vertex index")?;
+    Ok((
+        position,
+        index(fields.next(), uvs)?,
+        index(fields.next(), normals)?,
+    ))
+}
+
+// Ear clipping in the plane of the polygon, so concave faces are split correctly.
+// Whatever remains once no ear is found, for a self-intersecting face, is fanned.
+pub(crate) fn triangulate(points: &[Point3]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method, which gives the polygon normal even for concave faces.
+    let mut normal = Vec3::zeros();
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        normal += Vec3::from(
+            (a.y() - b.y()) * (a.z() + b.z()),
+            (a.z() - b.z()) * (a.x() + b.x()),
+            (a.x() - b.x()) * (a.y() + b.y()),
+        );
+    }
+    let left_of = |a: Point3, b: Point3, p: Point3| (b - a).cross(p - a).dot(normal) >= 0.0;
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let [a, b, c] = [
+                remaining[(i + m - 1) % m],
+                remaining[i],
+                remaining[(i + 1) % m],
+            ];
+            let (pa, pb, pc) = (points[a], points[b], points[c]);
+            if (pb - pa).cross(pc - pb).dot(normal) <= 0.0 {
+                return false;
+            }
+            !remaining.iter().any(|&k| {
+                let p = points[k];
+                ![a, b, c].contains(&k)
+                    && left_of(pa, pb, p)
+                    && left_of(pb, pc, p)
+                    && left_of(pc, pa, p)
+            })
+        });
+        let Some(i) = ear else {
+            break;
+        };
+        triangles.push([
+            remaining[(i + m - 1) % m],
+            remaining[i],
+            remaining[(i + 1) % m],
+        ]);
+        remaining.remove(i);
+    }
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
+
+// Build the mesh of a group, with one vertex per distinct corner. Normals are only
+// kept when every corner of the group has one.
+fn mesh(
+    group: &Group,
+    positions: &[Point3],
+    uvs: &[(f64, f64)],
+    normals: &[Vec3],
+    mat: Arc<dyn Material>,
+) -> TriangleMesh {
+    let mut vertices: HashMap<Corner, u32> = HashMap::new();
+    let mut corners = Vec::new();
+    let indices = group
+        .triangles
+        .iter()
+        .map(|triangle| {
+            triangle.map(|c| {
+                *vertices.entry(c).or_insert_with(|| {
+                    corners.push(c);
+                    corners.len() as u32 - 1
+                })
+            })
+        })
+        .collect();
+
+    let mut mesh = TriangleMesh::from(
+        corners.iter().map(|c| positions[c.0]).collect(),
+        indices,
+        mat,
+    );
+    if corners.iter().any(|c| c.1.is_some()) {
+        mesh = mesh.with_uvs(
+            corners
+                .iter()
+                .map(|c| c.1.map_or((0.0, 0.0), |i| uvs[i]))
+                .collect(),
+        );
+    }
+    if corners
+        .iter()
+        .all(|c| c.2.is_some_and(|i| !normals[i].near_zero()))
+    {
+        mesh = mesh.with_normals(
+            corners
+                .iter()
+                .filter_map(|c| c.2)
+                .map(|i| normals[i])
+                .collect(),
+        );
+    }
+    mesh
+}
+
+// The subset of an MTL material that maps onto the materials of the renderer.
+#[derive(Default)]
+struct MtlDesc {
+    kd: Option<Color>,
+    ks: Option<Color>,
+    ke: Option<Color>,
+    ns: Option<f64>,
+    ni: Option<f64>,
+    d: Option<f64>,
+    map_kd: Option<PathBuf>,
+}
+
+fn load_mtl(path: &Path) -> Result<HashMap<String, MtlDesc>> {
+    let text = read(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let syntax = |line: usize, message: String| Error::Syntax {
+        path: path.into(),
+        line,
+        message,
+    };
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlDesc)> = None;
+    for (n, line) in text.lines().enumerate() {
+        let n = n + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        if keyword == "newmtl" {
+            materials.extend(current.take());
+            current = Some((tokens.collect::<Vec<_>>().join(" "), MtlDesc::default()));
+            continue;
+        }
+        let Some((_, desc)) = current.as_mut() else {
+            return Err(syntax(n, format!("{} before any newmtl", keyword)));
+        };
+        let color = |tokens| -> Result<Color> {
+            match floats(tokens, 1).map_err(|e| syntax(n, e))?[..] {
+                [r, g, b, ..] => Ok(Color::from(r, g, b)),
+                [v, ..] => Ok(Color::from(v, v, v)),
+                [] => unreachable!(),
+            }
+        };
+        let float = |tokens| -> Result<f64> { Ok(floats(tokens, 1).map_err(|e| syntax(n, e))?[0]) };
+        match keyword {
+            "Kd" => desc.kd = Some(color(tokens)?),
+            "Ks" => desc.ks = Some(color(tokens)?),
+            "Ke" => desc.ke = Some(color(tokens)?),
+            "Ns" => desc.ns = Some(float(tokens)?),
+            "Ni" => desc.ni = Some(float(tokens)?),
+            "d" => desc.d = Some(float(tokens)?),
+            "Tr" => desc.d = Some(1.0 - float(tokens)?),
+            // Texture options come first, the file name is last.
+            "map_Kd" => match tokens.last() {
+                Some(file) => desc.map_kd = Some(dir.join(file)),
+                None => return Err(syntax(n, "missing texture file".to_string())),
+            },
+            _ => {}
+        }
+    }
+    materials.extend(current);
+    Ok(materials)
+}
+
+impl MtlDesc {
+    // Emissive materials become lights, transparent ones glass, those more specular
+    // than diffuse metal, with the Phong exponent turned into fuzz, and the others
+    // Lambertian, textured by map_Kd when given.
+    fn material(&self) -> Result<Arc<dyn Material>> {
+        let max = |c: Color| c.x().max(c.y()).max(c.z());
+        if let Some(ke) = self.ke.filter(|&ke| max(ke) > 0.0) {
+            return Ok(Arc::new(DiffuseLight::from_color(ke)));
+        }
+        if self.d.is_some_and(|d| d < 1.0) {
+            return Ok(Arc::new(Dielectric::try_new(self.ni.unwrap_or(1.5))?));
+        }
+        let kd = self.kd.unwrap_or(Color::from(0.8, 0.8, 0.8));
+        if let Some(ks) = self.ks.filter(|&ks| max(ks) > 0.0 && max(ks) >= max(kd)) {
+            let ns = self.ns.unwrap_or(0.0).max(0.0);
+            return Ok(Arc::new(Metal::try_new(ks, (2.0 / (ns + 2.0)).sqrt())?));
+        }
+        match &self.map_kd {
+            Some(path) => Ok(Arc::new(Lambertian::from_texture(Arc::new(
+                ImageTexture::from(path.to_string_lossy())?,
+            )))),
+            None => Ok(Arc::new(Lambertian::from_color(kd))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::interval::Interval;
+    use crate::ray::Ray;
+
+    fn load_text(name: &str, text: &str) -> Result<HittableList> {
+        let path =
+            std::env::temp_dir().join(format!("ray_tracer_rs-{}-{}", std::process::id(), name));
+        fs::write(&path, text).unwrap();
+        let list = load(&path, Arc::new(Lambertian::from_color(Color::ones())));
+        fs::remove_file(&path).unwrap();
+        list
+    }
+
+    // Distance to the list along a ray going down z from above (x, y).
+    fn depth_at(list: &HittableList, x: f64, y: f64) -> Option<f64> {
+        let r = Ray::from(Point3::from(x, y, 1.0), Vec3::from(0.0, 0.0, -1.0), 0.0);
+        let mut rec = HitRecord::new();
+        list.hit(&r, Interval::from(0.001, f64::INFINITY), &mut rec)
+            .then_some(rec.t)
+    }
+
+    #[test]
+    fn concave_faces_are_ear_clipped() {
+        // An L whose notch, above and right of (1, 1), a fan from the first corner
+        // would partly cover.
+        let points = [(1, 2), (0, 2), (0, 0), (2, 0), (2, 1), (1, 1)]
+            .map(|(x, y)| Point3::from(x as f64, y as f64, 0.0));
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), points.len() - 2);
+        let mut area = 0.0;
+        for [a, b, c] in triangles {
+            let (pa, pb, pc) = (points[a], points[b], points[c]);
+            let z = (pb - pa).cross(pc - pa).z();
+            assert!(z > 0.0, "triangle {:?} is flipped", [a, b, c]);
+            area += z / 2.0;
+        }
+        assert_eq!(area, 3.0);
+
+        let list = load_text(
+            "concave.obj",
+            "v 1 2 0\nv 0 2 0\nv 0 0 0\nv 2 0 0\nv 2 1 0\nv 1 1 0\nf 1 2 3 4 5 6\n",
+        )
+        .unwrap();
+        assert_eq!(depth_at(&list, 0.5, 1.5), Some(1.0));
+        assert_eq!(depth_at(&list, 1.2, 1.2), None);
+    }
+
+    #[test]
+    fn negative_indices_count_back() {
+        assert_eq!(corner("-1", (4, 0, 0)), Ok((3, None, None)));
+        assert_eq!(corner("-4/-1/-2", (4, 2, 2)), Ok((0, Some(1), Some(0))));
+        assert_eq!(corner("2//-1", (4, 0, 3)), Ok((1, None, Some(2))));
+        assert!(corner("-5", (4, 0, 0)).is_err());
+        assert!(corner("0", (4, 0, 0)).is_err());
+
+        // Relative to the vertices read so far, not to the whole file.
+        let list = load_text(
+            "negative.obj",
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nf -3 -2 -1\nv 5 5 0\nv 6 5 0\nv 6 6 0\nf -3 -2 -1\n",
+        )
+        .unwrap();
+        assert_eq!(depth_at(&list, 0.9, 0.1), Some(1.0));
+        assert_eq!(depth_at(&list, 5.9, 5.1), Some(1.0));
+        assert_eq!(depth_at(&list, 3.0, 3.0), None);
+    }
+}
@@ -81,7 +81,7 @@ impl Perlin {
         }
         accum
     }
-    fn permute(p: &mut Vec<i32>, n: i32) {
+    fn permute(p: &mut [i32], n: i32) {
         for i in (0..n - 1).rev() {
             let target = random_int(0, i);
             let tmp = p[i as usize];
@@ -0,0 +1,404 @@
+use std::fs;
+use std::path::Path;
+use std::str::SplitAsciiWhitespace;
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::error::{Error, Result};
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+use crate::obj::triangulate;
+use crate::vec3::{Point3, Vec3};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+enum Property {
+    Scalar(Scalar, String),
+    List {
+        count: Scalar,
+        item: Scalar,
+        name: String,
+    },
+}
+
+type Header<'a> = (Format, Vec<Element>, &'a [u8]);
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Read a PLY mesh, ASCII or binary of either endianness. Vertex normals, texture
+// coordinates and colors are kept when present, the colors being read by the
+// `VertexColor` texture. Polygons are split into triangles.
+pub fn load<P: AsRef<Path>>(path: P, mat: Arc<dyn Material>) -> Result<TriangleMesh> {
+    let path = path.as_ref();
+    let data = fs::read(path).map_err(|source| Error::Io {
+        path: path.into(),
+        source,
+    })?;
+    let (format, elements, body) = header(&data).map_err(|(line, message)| Error::Syntax {
+        path: path.into(),
+        line,
+        message,
+    })?;
+    let malformed = |message: String| Error::Malformed {
+        path: path.into(),
+        message,
+    };
+
+    let text;
+    let mut body = match format {
+        Format::Ascii => {
+            text = String::from_utf8_lossy(body);
+            Body::Ascii(text.split_ascii_whitespace())
+        }
+        _ => Body::Binary {
+            data: body,
+            big_endian: format == Format::BigEndian,
+        },
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut colors = Vec::new();
+    let mut faces: Vec<Vec<i64>> = Vec::new();
+    for element in &elements {
+        let find = |names: &[&str]| {
+            element.properties.iter().position(|p| match p {
+                Property::Scalar(_, name) => names.contains(&name.as_str()),
+                Property::List { .. } => false,
+            })
+        };
+        let xyz = [find(&["x"]), find(&["y"]), find(&["z"])];
+        let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+        let uv = [
+            find(&["s", "u", "texture_u"]),
+            find(&["t", "v", "texture_v"]),
+        ];
+        let rgb = [
+            find(&["red", "diffuse_red"]),
+            find(&["green", "diffuse_green"]),
+            find(&["blue", "diffuse_blue"]),
+        ];
+        let indices = element.properties.iter().position(|p| {
+            matches!(p, Property::List { name, .. } if name == "vertex_indices" || name == "vertex_index")
+        });
+        if element.name == "vertex" && xyz.contains(&None) {
+            return Err(malformed("vertices need x, y and z".to_string()));
+        }
+
+        let mut scalars = vec![0.0; element.properties.len()];
+        let mut list = Vec::new();
+        for row in 0..element.count {
+            for (i, property) in element.properties.iter().enumerate() {
+                let read = |body: &mut Body, ty| {
+                    body.read(ty)
+                        .map_err(|e| malformed(format!("{} {}: {}", element.name, row, e)))
+                };
+                match property {
+                    Property::Scalar(ty, _) => scalars[i] = read(&mut body, *ty)?,
+                    Property::List { count, item, .. } => {
+                        let count = read(&mut body, *count)? as usize;
+                        let items = (0..count)
+                            .map(|_| read(&mut body, *item))
+                            .collect::<Result<Vec<_>>>()?;
+                        if Some(i) == indices {
+                            list = items;
+                        }
+                    }
+                }
+            }
+
+            match element.name.as_str() {
+                "vertex" => {
+                    let [x, y, z] = xyz.map(|p| scalars[p.unwrap()]);
+                    positions.push(Point3::from(x, y, z));
+                    if let [Some(x), Some(y), Some(z)] = normal {
+                        normals.push(Vec3::from(scalars[x], scalars[y], scalars[z]));
+                    }
+                    if let [Some(u), Some(v)] = uv {
+                        uvs.push((scalars[u], scalars[v]));
+                    }
+                    if let [Some(r), Some(g), Some(b)] = rgb {
+                        let channel = |p: usize| match &element.properties[p] {
+                            Property::Scalar(ty, _) => scalars[p] / ty.unit(),
+                            Property::List { .. } => unreachable!(),
+                        };
+                        colors.push(Color::from(channel(r), channel(g), channel(b)));
+                    }
+                }
+                "face" if indices.is_some() => {
+                    faces.push(list.iter().map(|&i| i as i64).collect());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut triangles = Vec::with_capacity(faces.len());
+    for face in faces.iter().filter(|f| f.len() >= 3) {
+        if let Some(i) = face
+            .iter()
+            .find(|&&i| !(0..positions.len() as i64).contains(&i))
+        {
+            return Err(malformed(format!(
+                "vertex index {} is out of range for {} vertices",
+                i,
+                positions.len()
+            )));
+        }
+        let points: Vec<Point3> = face.iter().map(|&i| positions[i as usize]).collect();
+        for corners in triangulate(&points) {
+            triangles.push(corners.map(|c| face[c] as u32));
+        }
+    }
+
+    let count = positions.len();
+    let mut mesh = TriangleMesh::from(positions, triangles, mat);
+    if normals.len() == count && !normals.iter().any(|n| n.near_zero()) {
+        mesh = mesh.with_normals(normals);
+    }
+    if uvs.len() == count {
+        mesh = mesh.with_uvs(uvs);
+    }
+    if colors.len() == count {
+        mesh = mesh.with_colors(colors);
+    }
+    Ok(mesh)
+}
+
+// Parse the header, returning the format, the elements it declares and the body
+// that follows. Errors carry the header line they were found on.
+fn header(data: &[u8]) -> std::result::Result<Header<'_>, (usize, String)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut rest = data;
+
+    for n in 1.. {
+        let Some(end) = rest.iter().position(|&b| b == b'\n') else {
+            return Err((n, "missing end_header".to_string()));
+        };
+        let line = String::from_utf8_lossy(&rest[..end]);
+        rest = &rest[end + 1..];
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens[..] {
+            ["ply"] if n == 1 => {}
+            _ if n == 1 => return Err((n, "not a PLY file".to_string())),
+            ["format", kind, _] => {
+                format = Some(match kind {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err((n, format!("unknown format {}", kind))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| (n, format!("invalid element count {}", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let property = Property::List {
+                    count: scalar(count).ok_or((n, format!("unknown type {}", count)))?,
+                    item: scalar(item).ok_or((n, format!("unknown type {}", item)))?,
+                    name: name.to_string(),
+                };
+                match elements.last_mut() {
+                    Some(element) => element.properties.push(property),
+                    None => return Err((n, "property before any element".to_string())),
+                }
+            }
+            ["property", ty, name] => {
+                let ty = scalar(ty).ok_or((n, format!("unknown type {}", ty)))?;
+                match elements.last_mut() {
+                    Some(element) => element
+                        .properties
+                        .push(Property::Scalar(ty, name.to_string())),
+                    None => return Err((n, "property before any element".to_string())),
+                }
+            }
+            ["end_header"] => break,
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err((n, format!("unexpected header line: {}", line.trim()))),
+        }
+    }
+
+    match format {
+        Some(format) => Ok((format, elements, rest)),
+        None => Err((1, "missing format".to_string())),
+    }
+}
+
+fn scalar(name: &str) -> Option<Scalar> {
+    Some(match name {
+        "char" | "int8" => Scalar::I8,
+        "uchar" | "uint8" => Scalar::U8,
+        "short" | "int16" => Scalar::I16,
+        "ushort" | "uint16" => Scalar::U16,
+        "int" | "int32" => Scalar::I32,
+        "uint" | "uint32" => Scalar::U32,
+        "float" | "float32" => Scalar::F32,
+        "double" | "float64" => Scalar::F64,
+        _ => return None,
+    })
+}
+
+impl Scalar {
+    // The value standing for full intensity when the type holds a color channel.
+    fn unit(self) -> f64 {
+        match self {
+            Scalar::U8 => u8::MAX as f64,
+            Scalar::U16 => u16::MAX as f64,
+            _ => 1.0,
+        }
+    }
+}
+
+enum Body<'a> {
+    Ascii(SplitAsciiWhitespace<'a>),
+    Binary { data: &'a [u8], big_endian: bool },
+}
+
+impl Body<'_> {
+    fn read(&mut self, ty: Scalar) -> std::result::Result<f64, String> {
+        match self {
+            Body::Ascii(tokens) => {
+                let token = tokens.next().ok_or("unexpected end of file")?;
+                token
+                    .parse()
+                    .map_err(|_| format!("invalid number: {}", token))
+            }
+            Body::Binary { data, big_endian } => {
+                macro_rules! take {
+                    ($t:ty) => {{
+                        const N: usize = std::mem::size_of::<$t>();
+                        if data.len() < N {
+                            return Err("unexpected end of file".to_string());
+                        }
+                        let bytes: [u8; N] = data[..N].try_into().unwrap();
+                        *data = &data[N..];
+                        match big_endian {
+                            true => <$t>::from_be_bytes(bytes) as f64,
+                            false => <$t>::from_le_bytes(bytes) as f64,
+                        }
+                    }};
+                }
+                Ok(match ty {
+                    Scalar::I8 => take!(i8),
+                    Scalar::U8 => take!(u8),
+                    Scalar::I16 => take!(i16),
+                    Scalar::U16 => take!(u16),
+                    Scalar::I32 => take!(i32),
+                    Scalar::U32 => take!(u32),
+                    Scalar::F32 => take!(f32),
+                    Scalar::F64 => take!(f64),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::interval::Interval;
+    use crate::material::Lambertian;
+    use crate::ray::Ray;
+
+    fn load_bytes(name: &str, data: &[u8]) -> Result<TriangleMesh> {
+        let path =
+            std::env::temp_dir().join(format!("ray_tracer_rs-{}-{}", std::process::id(), name));
+        fs::write(&path, data).unwrap();
+        let mesh = load(&path, Arc::new(Lambertian::from_color(Color::ones())));
+        fs::remove_file(&path).unwrap();
+        mesh
+    }
+
+    // The hit of a ray going down z from above (x, y).
+    fn hit_at(mesh: &TriangleMesh, x: f64, y: f64) -> Option<HitRecord> {
+        let r = Ray::from(Point3::from(x, y, 1.0), Vec3::from(0.0, 0.0, -1.0), 0.0);
+        let mut rec = HitRecord::new();
+        mesh.hit(&r, Interval::from(0.001, f64::INFINITY), &mut rec)
+            .then_some(rec)
+    }
+
+    // A unit square of float vertices with uchar colors, as one quad face whose
+    // list has a uchar count and int indices, in the given byte order.
+    fn square(big_endian: bool) -> Vec<u8> {
+        let format = match big_endian {
+            true => "binary_big_endian",
+            false => "binary_little_endian",
+        };
+        let mut data = format!(
+            "ply\nformat {} 1.0\nelement vertex 4\nproperty float x\nproperty float y\n\
+             property float z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\n\
+             element face 1\nproperty list uchar int vertex_indices\nend_header\n",
+            format
+        )
+        .into_bytes();
+        for (x, y) in [(0.0f32, 0.0f32), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            for v in [x, y, 0.0] {
+                data.extend(match big_endian {
+                    true => v.to_be_bytes(),
+                    false => v.to_le_bytes(),
+                });
+            }
+            data.extend([255, 0, 51]);
+        }
+        data.push(4);
+        for i in [0i32, 1, 2, 3] {
+            data.extend(match big_endian {
+                true => i.to_be_bytes(),
+                false => i.to_le_bytes(),
+            });
+        }
+        data
+    }
+
+    #[test]
+    fn binary_in_either_byte_order() {
+        for big_endian in [true, false] {
+            let mesh = load_bytes("square.ply", &square(big_endian)).unwrap();
+            assert_eq!(mesh.len(), 2, "the quad is split in two");
+            let rec = hit_at(&mesh, 0.25, 0.75).expect("the square is hit");
+            assert_eq!(rec.t, 1.0);
+            assert_eq!(rec.color, Some(Color::from(1.0, 0.0, 0.2)));
+            assert!(hit_at(&mesh, 1.5, 0.5).is_none());
+        }
+    }
+
+    #[test]
+    fn truncated_binary_is_malformed() {
+        let data = square(false);
+        let error = load_bytes("truncated.ply", &data[..data.len() - 2]).err();
+        assert!(
+            matches!(error, Some(Error::Malformed { .. })),
+            "{:?}",
+            error
+        );
+    }
+}
@@ -8,12 +8,12 @@ use crate::{
     vec3::Vec3,
     Point3,
 };
-use std::rc::Rc;
+use std::sync::Arc;
 pub struct Quad {
     q: Point3,
     u: Vec3,
     v: Vec3,
-    mat: Rc<dyn Material>,
+    mat: Arc<dyn Material>,
     bbox: Aabb,
     normal: Vec3,
     d: f64,
@@ -21,7 +21,7 @@ pub struct Quad {
 }
 
 impl Quad {
-    pub fn from(q: Point3, u: Vec3, v: Vec3, mat: Rc<dyn Material>) -> Self {
+    pub fn from(q: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Material>) -> Self {
         let n = u.cross(v);
         let normal = n.unit_vector();
         let d = normal.dot(q);
@@ -37,38 +37,55 @@ impl Quad {
             w,
         }
     }
+    // Both diagonals, as with `u` and `v` pointing different ways along an axis the
+    // corners `q + u` and `q + v` reach past `q` and `q + u + v`.
     pub fn set_bounding_box(q: Point3, u: Vec3, v: Vec3) -> Aabb {
-        Aabb::from_points(q, q + u + v).pad()
+        Aabb::from_bbox(
+            Aabb::from_points(q, q + u + v),
+            Aabb::from_points(q + u, q + v),
+        )
+        .pad()
     }
     pub fn is_interior(a: f64, b: f64, rec: &mut HitRecord) -> bool {
         // Given the hit point in plane coordinates, return false if it is outside the
         // primitive, otherwise set the hit record UV coordinates and return true.
-        if a < 0.0 || 1.0 < a || b < 0.0 || 1.0 < b {
+        if !Self::inside(a, b) {
             return false;
         }
         rec.u = a;
         rec.v = b;
         true
     }
-}
-
-impl Hittable for Quad {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn inside(a: f64, b: f64) -> bool {
+        (0.0..=1.0).contains(&a) && (0.0..=1.0).contains(&b)
+    }
+    // Where the ray meets the plane within `ray_t`: t, the point and its plane
+    // coordinates, which may lie outside the primitive.
+    fn plane_hit(&self, r: &Ray, ray_t: Interval) -> Option<(f64, Point3, f64, f64)> {
         let dnom = self.normal.dot(r.direction());
         // No hit if the ray is parallel to the plane.
         if dnom.abs() < 1e-8 {
-            return false;
+            return None;
         }
-        // Return false if the hit point parameter t is outside the ray interval.
+        // No hit if the hit point parameter t is outside the ray interval.
         let t = (self.d - self.normal.dot(r.origin())) / dnom;
         if !ray_t.contains(t) {
-            return false;
+            return None;
         }
-        // Determine the hit point lies within the planar shape using its plane coordinates.
         let intersection = r.at(t);
         let plana_hipt_vector = intersection - self.q;
         let alpha = self.w.dot(plana_hipt_vector.cross(self.v));
         let betha = self.w.dot(self.u.cross(plana_hipt_vector));
+        Some((t, intersection, alpha, betha))
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let Some((t, intersection, alpha, betha)) = self.plane_hit(r, ray_t) else {
+            return false;
+        };
+        // Determine the hit point lies within the planar shape using its plane coordinates.
         if !Self::is_interior(alpha, betha, rec) {
             return false;
         }
@@ -76,15 +93,20 @@ impl Hittable for Quad {
         rec.t = t;
         rec.p = intersection;
         rec.mat = self.mat.clone();
+        rec.color = None;
         rec.set_face_normal(r, &self.normal);
         true
     }
+    fn occluded(&self, r: &Ray, ray_t: Interval) -> bool {
+        self.plane_hit(r, ray_t)
+            .is_some_and(|(_, _, alpha, betha)| Self::inside(alpha, betha))
+    }
     fn bounding_box(&self) -> Aabb {
         self.bbox
     }
 }
 
-pub fn box_volume(a: Point3, b: Point3, mat: Rc<dyn Material>) -> HittableList {
+pub fn box_volume(a: Point3, b: Point3, mat: Arc<dyn Material>) -> HittableList {
     let mut sides = HittableList::new();
 
     let min = Point3::from(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
@@ -94,37 +116,37 @@ pub fn box_volume(a: Point3, b: Point3, mat: Rc<dyn Material>) -> HittableList {
     let dy = Vec3::from(0.0, max.y() - min.y(), 0.0);
     let dz = Vec3::from(0.0, 0.0, max.z() - min.z());
 
-    sides.add(Rc::new(Quad::from(
+    sides.add(Arc::new(Quad::from(
         Point3::from(min.x(), min.y(), max.z()),
         dx,
         dy,
         mat.clone(),
     )));
-    sides.add(Rc::new(Quad::from(
+    sides.add(Arc::new(Quad::from(
         Point3::from(max.x(), min.y(), max.z()),
         dz * -1.0,
         dy,
         mat.clone(),
     )));
-    sides.add(Rc::new(Quad::from(
+    sides.add(Arc::new(Quad::from(
         Point3::from(max.x(), min.y(), min.z()),
         dx * -1.0,
         dy,
         mat.clone(),
     )));
-    sides.add(Rc::new(Quad::from(
+    sides.add(Arc::new(Quad::from(
         Point3::from(min.x(), min.y(), min.z()),
         dz,
         dy,
         mat.clone(),
     )));
-    sides.add(Rc::new(Quad::from(
+    sides.add(Arc::new(Quad::from(
         Point3::from(min.x(), max.y(), max.z()),
         dx,
         dz * -1.0,
         mat.clone(),
     )));
-    sides.add(Rc::new(Quad::from(
+    sides.add(Arc::new(Quad::from(
         Point3::from(min.x(), min.y(), min.z()),
         dx,
         dz,
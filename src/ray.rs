@@ -36,3 +36,9 @@ impl Ray {
         self.origin + self.direction * t
     }
 }
+
+impl Default for Ray {
+    fn default() -> Self {
+        Self::new()
+    }
+}
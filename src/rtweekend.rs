@@ -1,14 +1,55 @@
-use std::f64::{consts::PI, INFINITY};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::cell::RefCell;
+use std::f64::consts::PI;
 
-pub const INF: f64 = INFINITY;
+pub const INF: f64 = f64::INFINITY;
 pub const RAND_MAX: i32 = 0;
+pub const DEFAULT_SEED: u64 = 0;
+
+thread_local! {
+    // Every thread owns its generator so that render workers never contend on it,
+    // and reseeding one thread never disturbs the samples drawn by another.
+    // ChaCha8 output is fixed for a given seed, unlike `StdRng`, and every thread
+    // starts from the same seed so that scenes build identically on each run.
+    static RNG: RefCell<ChaCha8Rng> = RefCell::new(ChaCha8Rng::seed_from_u64(DEFAULT_SEED));
+}
 
 pub fn degrees2radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
 
+// Seeds the generator of the calling thread. Scene construction, including the
+// Perlin lattices, draws from it; the camera reseeds each pixel from its own `seed`.
+pub fn seed_random(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = ChaCha8Rng::seed_from_u64(seed));
+}
+
+// A generator of its own, starting where the generator of the calling thread was
+// last seeded, for draws that follow the scene seed without moving its stream.
+pub fn seeded_rng() -> ChaCha8Rng {
+    RNG.with(|rng| ChaCha8Rng::from_seed(rng.borrow().get_seed()))
+}
+
+pub fn mix_seed(seed: u64, index: u64) -> u64 {
+    // SplitMix64 finalizer, so neighbouring indices get unrelated streams.
+    let mut z = seed ^ index.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// A number in [0, 1) fixed by `values`, for draws that must leave the stream of the
+// thread where it is.
+pub fn hash_double(values: &[f64]) -> f64 {
+    let hash = values
+        .iter()
+        .fold(DEFAULT_SEED, |hash, value| mix_seed(hash, value.to_bits()));
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
 pub fn random_double() -> f64 {
-    rand::random::<f64>() / (RAND_MAX as f64 + 1.0)
+    RNG.with(|rng| rng.borrow_mut().gen::<f64>()) / (RAND_MAX as f64 + 1.0)
 }
 
 pub fn random_double_interval(min: f64, max: f64) -> f64 {
@@ -17,3 +58,19 @@ pub fn random_double_interval(min: f64, max: f64) -> f64 {
 pub fn random_int(min: i32, max: i32) -> i32 {
     random_double_interval(min as f64, max as f64 + 1.0) as i32
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_rng_restarts_from_the_thread_seed() {
+        let mut expected = ChaCha8Rng::seed_from_u64(7);
+        seed_random(7);
+        let first = random_double();
+        assert_eq!(first, expected.gen::<f64>());
+        assert_eq!(seeded_rng().gen::<f64>(), first);
+        // The thread stream goes on where it was.
+        assert_eq!(random_double(), expected.gen::<f64>());
+    }
+}
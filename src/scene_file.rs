@@ -0,0 +1,830 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+
+use crate::animation::{Animation, CameraKey};
+use crate::bvh::{BvhNode, BvhOptions, BvhSplit};
+use crate::camera::{Camera, CameraKeyframe};
+use crate::constant_medium::ConstantMedium;
+use crate::error::{Error, Result};
+use crate::gltf;
+use crate::hittable::{AnimatedInstance, Hittable, Instance, RotateY, Translate};
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+use crate::mesh::TriangleMesh;
+use crate::obj;
+use crate::ply;
+use crate::quad::{box_volume, Quad};
+use crate::sphere::Sphere;
+use crate::stl;
+use crate::texture::{
+    CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture, VertexColor,
+};
+use crate::transform::{AnimatedTransform, Keyframe, Transform};
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+
+// JSON scene description: the camera, named textures and materials, and the list
+// of objects referring to them by name.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    #[serde(default)]
+    camera: CameraDesc,
+    #[serde(default)]
+    textures: BTreeMap<String, TextureDesc>,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialDesc>,
+    objects: Vec<NamedObject>,
+    animation: Option<AnimationDesc>,
+}
+
+// Frames to render by default and the keyed camera. Camera settings a key leaves
+// out keep the values of the scene camera.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnimationDesc {
+    frames: Option<[u32; 2]>,
+    fps: Option<f64>,
+    #[serde(default)]
+    camera: Vec<CameraKeyDesc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraKeyDesc {
+    time: f64,
+    lookfrom: Option<[f64; 3]>,
+    lookat: Option<[f64; 3]>,
+    vfov: Option<f64>,
+    focus_dist: Option<f64>,
+    defocus_angle: Option<f64>,
+}
+
+// Missing camera settings keep the values of `Camera::default`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraDesc {
+    aspect_ratio: Option<f64>,
+    image_width: Option<i32>,
+    samples_per_pixel: Option<i32>,
+    max_depth: Option<i32>,
+    vfov: Option<f64>,
+    lookfrom: Option<[f64; 3]>,
+    lookat: Option<[f64; 3]>,
+    vup: Option<[f64; 3]>,
+    defocus_angle: Option<f64>,
+    focus_dist: Option<f64>,
+    background: Option<[f64; 3]>,
+    shutter_open: Option<f64>,
+    shutter_close: Option<f64>,
+    shutter_curve: Option<Vec<[f64; 2]>>,
+    motion: Option<Vec<CameraKeyframeDesc>>,
+}
+
+// A camera pose during the exposure. A point left out keeps the camera's own.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraKeyframeDesc {
+    time: f64,
+    lookfrom: Option<[f64; 3]>,
+    lookat: Option<[f64; 3]>,
+}
+
+// A texture given either inline as a solid color or by name.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TextureRef {
+    Color([f64; 3]),
+    Named(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid {
+        color: [f64; 3],
+    },
+    Checker {
+        scale: f64,
+        even: TextureRef,
+        odd: TextureRef,
+    },
+    Image {
+        path: String,
+    },
+    Noise {
+        scale: f64,
+        #[serde(default = "default_point_count")]
+        point_count: i32,
+    },
+    VertexColor {
+        #[serde(default = "default_gray")]
+        fallback: [f64; 3],
+    },
+}
+
+fn default_gray() -> [f64; 3] {
+    [0.8, 0.8, 0.8]
+}
+
+fn default_point_count() -> i32 {
+    256
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian {
+        albedo: TextureRef,
+    },
+    Metal {
+        albedo: [f64; 3],
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dielectric {
+        ir: f64,
+    },
+    DiffuseLight {
+        emit: TextureRef,
+    },
+    Isotropic {
+        albedo: TextureRef,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    MovingSphere {
+        center1: [f64; 3],
+        center2: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Quad {
+        q: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        normals: Option<[[f64; 3]; 3]>,
+        uvs: Option<[[f64; 2]; 3]>,
+        material: String,
+    },
+    Mesh {
+        vertices: Vec<[f64; 3]>,
+        indices: Vec<[u32; 3]>,
+        normals: Option<Vec<[f64; 3]>>,
+        uvs: Option<Vec<[f64; 2]>>,
+        material: String,
+    },
+    Obj {
+        path: PathBuf,
+        material: Option<String>, // For faces without a `usemtl`
+    },
+    Gltf {
+        path: PathBuf, // Its cameras are ignored, the scene file has its own
+    },
+    Ply {
+        path: PathBuf,
+        material: Option<String>,
+    },
+    Stl {
+        path: PathBuf,
+        material: Option<String>,
+    },
+    Box {
+        a: [f64; 3],
+        b: [f64; 3],
+        material: String,
+    },
+    ConstantMedium {
+        boundary: Box<NamedObject>,
+        density: f64,
+        albedo: TextureRef,
+    },
+    Translate {
+        offset: [f64; 3],
+        object: Box<NamedObject>,
+    },
+    RotateY {
+        angle: f64,
+        object: Box<NamedObject>,
+    },
+    Transform {
+        transform: Vec<TransformStep>, // Applied in order
+        object: Box<NamedObject>,
+    },
+    Animate {
+        keyframes: Vec<KeyframeDesc>,
+        object: Box<NamedObject>,
+    },
+    List {
+        objects: Vec<NamedObject>,
+        #[serde(default)]
+        bvh: BvhDesc,
+    },
+}
+
+// `true` builds a BVH with the split the scene is loaded with, or a split can be named.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum BvhDesc {
+    Enabled(bool),
+    Split(SplitDesc),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SplitDesc {
+    Median,
+    Sah,
+}
+
+impl Default for BvhDesc {
+    fn default() -> Self {
+        BvhDesc::Enabled(false)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformStep {
+    Translate([f64; 3]),
+    Scale([f64; 3]),
+    RotateX(f64),
+    RotateY(f64),
+    RotateZ(f64),
+    Rotate { axis: [f64; 3], angle: f64 },
+    Matrix([[f64; 4]; 3]), // The top three rows of an affine matrix
+}
+
+// A pose at a ray time, in seconds: scale, then rotations about x, y and z in
+// degrees, then translation.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDesc {
+    time: f64,
+    #[serde(default)]
+    translate: [f64; 3],
+    #[serde(default)]
+    rotate: [f64; 3],
+    #[serde(default = "default_scale")]
+    scale: [f64; 3],
+}
+
+fn default_scale() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+// Any object may carry a `name`, used instead of its position in the file when
+// reporting errors about it.
+#[derive(Debug)]
+struct NamedObject {
+    name: Option<String>,
+    object: ObjectDesc,
+}
+
+impl<'de> Deserialize<'de> for NamedObject {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let mut value = serde_json::Value::deserialize(deserializer)?;
+        let name = match value.as_object_mut().and_then(|o| o.remove("name")) {
+            Some(name) => Some(String::deserialize(name).map_err(de::Error::custom)?),
+            None => None,
+        };
+        let object = ObjectDesc::deserialize(value).map_err(de::Error::custom)?;
+        Ok(NamedObject { name, object })
+    }
+}
+
+// Read a scene file and build its world and camera, with BVHs built as `bvh` says.
+// Relative image paths are resolved from the directory of the scene file. Textures,
+// materials and objects are checked while loading, the camera by `Camera::validate`
+// once overridden.
+pub fn load<P: AsRef<Path>>(path: P, bvh: BvhOptions) -> Result<(HittableList, Camera)> {
+    load_animation(path, bvh).map(|(world, cam, _)| (world, cam))
+}
+
+// Like `load`, along with the animation the scene file describes, if any.
+pub fn load_animation<P: AsRef<Path>>(
+    path: P,
+    bvh: BvhOptions,
+) -> Result<(HittableList, Camera, Option<Animation>)> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.into(),
+        source,
+    })?;
+    let desc: SceneDesc = serde_json::from_str(&text).map_err(|source| Error::Parse {
+        path: path.into(),
+        source,
+    })?;
+
+    let mut loader = Loader {
+        desc: &desc,
+        base: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        resolving: Vec::new(),
+        bvh,
+    };
+
+    let mut world = HittableList::new();
+    for (i, object) in desc.objects.iter().enumerate() {
+        world.add(loader.named_object(object, format!("objects[{}]", i))?);
+    }
+
+    let cam = camera(&desc.camera);
+    let animation = desc.animation.as_ref().map(|desc| animation(desc, &cam));
+    Ok((world, cam, animation))
+}
+
+fn animation(desc: &AnimationDesc, cam: &Camera) -> Animation {
+    let mut animation = Animation::new();
+    if let Some([first, last]) = desc.frames {
+        animation.first_frame = first;
+        animation.last_frame = last;
+    }
+    if let Some(fps) = desc.fps {
+        animation.fps = fps;
+    }
+    animation.camera = desc
+        .camera
+        .iter()
+        .map(|key| CameraKey {
+            time: key.time,
+            lookfrom: key.lookfrom.map_or(cam.lookfrom, vec3),
+            lookat: key.lookat.map_or(cam.lookat, vec3),
+            vfov: key.vfov.unwrap_or(cam.vfov),
+            focus_dist: key.focus_dist.unwrap_or(cam.focus_dist),
+            defocus_angle: key.defocus_angle.unwrap_or(cam.defocus_angle),
+        })
+        .collect();
+    animation.camera.sort_by(|a, b| a.time.total_cmp(&b.time));
+    animation
+}
+
+fn vec3(v: [f64; 3]) -> Vec3 {
+    Vec3::from(v[0], v[1], v[2])
+}
+
+fn camera(desc: &CameraDesc) -> Camera {
+    let mut cam = Camera::default();
+    if let Some(aspect_ratio) = desc.aspect_ratio {
+        cam.aspect_ratio = aspect_ratio;
+    }
+    if let Some(image_width) = desc.image_width {
+        cam.image_width = image_width;
+    }
+    if let Some(samples_per_pixel) = desc.samples_per_pixel {
+        cam.samples_per_pixel = samples_per_pixel;
+    }
+    if let Some(max_depth) = desc.max_depth {
+        cam.max_depth = max_depth;
+    }
+    if let Some(vfov) = desc.vfov {
+        cam.vfov = vfov;
+    }
+    if let Some(lookfrom) = desc.lookfrom {
+        cam.lookfrom = vec3(lookfrom);
+    }
+    if let Some(lookat) = desc.lookat {
+        cam.lookat = vec3(lookat);
+    }
+    if let Some(vup) = desc.vup {
+        cam.vup = vec3(vup);
+    }
+    if let Some(defocus_angle) = desc.defocus_angle {
+        cam.defocus_angle = defocus_angle;
+    }
+    if let Some(focus_dist) = desc.focus_dist {
+        cam.focus_dist = focus_dist;
+    }
+    if let Some(background) = desc.background {
+        cam.backround = vec3(background);
+    }
+    if let Some(shutter_open) = desc.shutter_open {
+        cam.shutter_open = shutter_open;
+    }
+    if let Some(shutter_close) = desc.shutter_close {
+        cam.shutter_close = shutter_close;
+    }
+    if let Some(curve) = &desc.shutter_curve {
+        cam.shutter_curve = curve.iter().map(|[x, w]| (*x, *w)).collect();
+    }
+    if let Some(motion) = &desc.motion {
+        cam.motion = motion
+            .iter()
+            .map(|key| CameraKeyframe {
+                time: key.time,
+                lookfrom: key.lookfrom.map_or(cam.lookfrom, vec3),
+                lookat: key.lookat.map_or(cam.lookat, vec3),
+            })
+            .collect();
+    }
+    cam
+}
+
+struct Loader<'a> {
+    desc: &'a SceneDesc,
+    base: PathBuf,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    resolving: Vec<String>, // Textures being built, to catch reference cycles
+    bvh: BvhOptions,
+}
+
+impl Loader<'_> {
+    fn texture_ref(&mut self, texture: &TextureRef) -> Result<Arc<dyn Texture>> {
+        match texture {
+            TextureRef::Color(c) => Ok(Arc::new(SolidColor::from_color(vec3(*c)))),
+            TextureRef::Named(name) => self.texture(name),
+        }
+    }
+
+    fn texture(&mut self, name: &str) -> Result<Arc<dyn Texture>> {
+        if let Some(texture) = self.textures.get(name) {
+            return Ok(texture.clone());
+        }
+        if self.resolving.iter().any(|n| n == name) {
+            return Err(Error::CyclicTexture(name.to_string()));
+        }
+        let desc = self.desc;
+        let Some(texture_desc) = desc.textures.get(name) else {
+            return Err(Error::Undefined {
+                kind: "texture",
+                name: name.to_string(),
+            });
+        };
+
+        self.resolving.push(name.to_string());
+        let texture = self
+            .build_texture(texture_desc)
+            .map_err(|e| e.within(format!("textures.{}", name)));
+        self.resolving.pop();
+
+        let texture = texture?;
+        self.textures.insert(name.to_string(), texture.clone());
+        Ok(texture)
+    }
+
+    fn build_texture(&mut self, desc: &TextureDesc) -> Result<Arc<dyn Texture>> {
+        let texture: Arc<dyn Texture> = match desc {
+            TextureDesc::Solid { color } => Arc::new(SolidColor::from_color(vec3(*color))),
+            TextureDesc::Checker { scale, even, odd } => {
+                if *scale == 0.0 || !scale.is_finite() {
+                    return Err(Error::InvalidTexture(format!(
+                        "checker scale must be non-zero, got {}",
+                        scale
+                    )));
+                }
+                Arc::new(CheckerTexture::new(
+                    *scale,
+                    self.texture_ref(even)?,
+                    self.texture_ref(odd)?,
+                ))
+            }
+            TextureDesc::Image { path } => {
+                let path = self.base.join(path);
+                Arc::new(ImageTexture::from(path.to_string_lossy())?)
+            }
+            TextureDesc::Noise { scale, point_count } => {
+                // The lattice is indexed with the low 8 bits of the cell coordinates.
+                if *point_count < 256 {
+                    return Err(Error::InvalidTexture(format!(
+                        "noise point_count must be at least 256, got {}",
+                        point_count
+                    )));
+                }
+                Arc::new(NoiseTexture::from(*point_count, *scale))
+            }
+            TextureDesc::VertexColor { fallback } => Arc::new(VertexColor::from(vec3(*fallback))),
+        };
+        Ok(texture)
+    }
+
+    fn material(&mut self, name: &str) -> Result<Arc<dyn Material>> {
+        if let Some(material) = self.materials.get(name) {
+            return Ok(material.clone());
+        }
+        let desc = self.desc;
+        let Some(material_desc) = desc.materials.get(name) else {
+            return Err(Error::Undefined {
+                kind: "material",
+                name: name.to_string(),
+            });
+        };
+
+        let material = self
+            .build_material(material_desc)
+            .map_err(|e| e.within(format!("materials.{}", name)))?;
+        self.materials.insert(name.to_string(), material.clone());
+        Ok(material)
+    }
+
+    fn build_material(&mut self, desc: &MaterialDesc) -> Result<Arc<dyn Material>> {
+        let material: Arc<dyn Material> = match desc {
+            MaterialDesc::Lambertian { albedo } => {
+                Arc::new(Lambertian::from_texture(self.texture_ref(albedo)?))
+            }
+            MaterialDesc::Metal { albedo, fuzz } => Arc::new(Metal::try_new(vec3(*albedo), *fuzz)?),
+            MaterialDesc::Dielectric { ir } => Arc::new(Dielectric::try_new(*ir)?),
+            MaterialDesc::DiffuseLight { emit } => {
+                Arc::new(DiffuseLight::from_material(self.texture_ref(emit)?))
+            }
+            MaterialDesc::Isotropic { albedo } => {
+                Arc::new(Isotropic::from_texture(self.texture_ref(albedo)?))
+            }
+        };
+        Ok(material)
+    }
+
+    fn named_object(&mut self, desc: &NamedObject, path: String) -> Result<Arc<dyn Hittable>> {
+        let name = desc.name.clone().unwrap_or(path);
+        self.object(&desc.object).map_err(|e| e.within(name))
+    }
+
+    fn object(&mut self, desc: &ObjectDesc) -> Result<Arc<dyn Hittable>> {
+        let object: Arc<dyn Hittable> = match desc {
+            ObjectDesc::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                check_radius(*radius)?;
+                Arc::new(Sphere::new_stationnary(
+                    vec3(*center),
+                    *radius,
+                    self.material(material)?,
+                ))
+            }
+            ObjectDesc::MovingSphere {
+                center1,
+                center2,
+                radius,
+                material,
+            } => {
+                check_radius(*radius)?;
+                Arc::new(Sphere::new_moving(
+                    vec3(*center1),
+                    vec3(*center2),
+                    *radius,
+                    self.material(material)?,
+                ))
+            }
+            ObjectDesc::Quad { q, u, v, material } => {
+                if vec3(*u).cross(vec3(*v)).near_zero() {
+                    return Err(Error::DegenerateGeometry("quad has no area".to_string()));
+                }
+                Arc::new(Quad::from(
+                    vec3(*q),
+                    vec3(*u),
+                    vec3(*v),
+                    self.material(material)?,
+                ))
+            }
+            ObjectDesc::Triangle {
+                vertices,
+                normals,
+                uvs,
+                material,
+            } => {
+                let [a, b, c] = vertices.map(vec3);
+                if (b - a).cross(c - a).near_zero() {
+                    return Err(Error::DegenerateGeometry(
+                        "triangle has no area".to_string(),
+                    ));
+                }
+                let mut triangle = Triangle::from(a, b, c, self.material(material)?);
+                if let Some(normals) = normals {
+                    if normals.iter().any(|n| vec3(*n).near_zero()) {
+                        return Err(Error::DegenerateGeometry(
+                            "triangle normals must be non-zero".to_string(),
+                        ));
+                    }
+                    triangle = triangle.with_normals(normals.map(vec3));
+                }
+                if let Some(uvs) = uvs {
+                    triangle = triangle.with_uvs(uvs.map(|[u, v]| (u, v)));
+                }
+                Arc::new(triangle)
+            }
+            ObjectDesc::Mesh {
+                vertices,
+                indices,
+                normals,
+                uvs,
+                material,
+            } => {
+                if let Some(i) = indices
+                    .iter()
+                    .flatten()
+                    .find(|&&i| i as usize >= vertices.len())
+                {
+                    return Err(Error::DegenerateGeometry(format!(
+                        "mesh index {} is out of range for {} vertices",
+                        i,
+                        vertices.len()
+                    )));
+                }
+                let count_matches =
+                    |len: Option<usize>| len.is_none_or(|len| len == vertices.len());
+                if !count_matches(normals.as_ref().map(Vec::len))
+                    || !count_matches(uvs.as_ref().map(Vec::len))
+                {
+                    return Err(Error::DegenerateGeometry(
+                        "mesh normals and uvs need one entry per vertex".to_string(),
+                    ));
+                }
+                let mut mesh = TriangleMesh::from(
+                    vertices.iter().copied().map(vec3).collect(),
+                    indices.clone(),
+                    self.material(material)?,
+                );
+                if let Some(normals) = normals {
+                    mesh = mesh.with_normals(normals.iter().copied().map(vec3).collect());
+                }
+                if let Some(uvs) = uvs {
+                    mesh = mesh.with_uvs(uvs.iter().map(|&[u, v]| (u, v)).collect());
+                }
+                Arc::new(mesh)
+            }
+            ObjectDesc::Obj { path, material } => {
+                let default = match material {
+                    Some(material) => self.material(material)?,
+                    None => Arc::new(Lambertian::from_color(vec3(default_gray()))),
+                };
+                Arc::new(obj::load(self.base.join(path), default)?)
+            }
+            ObjectDesc::Gltf { path } => Arc::new(gltf::load(self.base.join(path), self.bvh)?.0),
+            // Models are colored by their vertex colors unless given a material.
+            ObjectDesc::Ply { path, material } | ObjectDesc::Stl { path, material } => {
+                let mat: Arc<dyn Material> = match material {
+                    Some(material) => self.material(material)?,
+                    None => Arc::new(Lambertian::from_texture(Arc::new(VertexColor::from(vec3(
+                        default_gray(),
+                    ))))),
+                };
+                let path = self.base.join(path);
+                match desc {
+                    ObjectDesc::Ply { .. } => Arc::new(ply::load(path, mat)?),
+                    _ => Arc::new(stl::load(path, mat)?),
+                }
+            }
+            ObjectDesc::Box { a, b, material } => {
+                let extent = vec3(*a) - vec3(*b);
+                if (0..3).any(|c: usize| extent[c].abs() < 1e-8) {
+                    return Err(Error::DegenerateGeometry("box has no volume".to_string()));
+                }
+                Arc::new(box_volume(vec3(*a), vec3(*b), self.material(material)?))
+            }
+            ObjectDesc::ConstantMedium {
+                boundary,
+                density,
+                albedo,
+            } => {
+                if !(*density > 0.0 && density.is_finite()) {
+                    return Err(Error::InvalidMaterial(format!(
+                        "medium density must be positive, got {}",
+                        density
+                    )));
+                }
+                Arc::new(ConstantMedium::from_texture(
+                    self.named_object(boundary, "boundary".to_string())?,
+                    *density,
+                    self.texture_ref(albedo)?,
+                ))
+            }
+            ObjectDesc::Translate { offset, object } => Arc::new(Translate::from(
+                self.named_object(object, "object".to_string())?,
+                vec3(*offset),
+            )),
+            ObjectDesc::RotateY { angle, object } => Arc::new(RotateY::from(
+                self.named_object(object, "object".to_string())?,
+                *angle,
+            )),
+            ObjectDesc::Transform { transform, object } => {
+                let mut combined = Transform::identity();
+                for (i, step) in transform.iter().enumerate() {
+                    let step =
+                        transform_step(step).map_err(|e| e.within(format!("transform[{}]", i)))?;
+                    combined = combined.then(step);
+                }
+                Arc::new(Instance::from(
+                    self.named_object(object, "object".to_string())?,
+                    combined,
+                ))
+            }
+            ObjectDesc::Animate { keyframes, object } => Arc::new(AnimatedInstance::from(
+                self.named_object(object, "object".to_string())?,
+                animated_transform(keyframes)?,
+            )),
+            ObjectDesc::List { objects, bvh } => {
+                let mut list = HittableList::new();
+                for (i, object) in objects.iter().enumerate() {
+                    list.add(self.named_object(object, format!("objects[{}]", i))?);
+                }
+                let split = match bvh {
+                    BvhDesc::Enabled(false) => None,
+                    BvhDesc::Enabled(true) => Some(self.bvh.split),
+                    BvhDesc::Split(SplitDesc::Median) => Some(BvhSplit::Median),
+                    BvhDesc::Split(SplitDesc::Sah) => Some(BvhSplit::Sah),
+                };
+                match split {
+                    Some(split) if !list.objects.is_empty() => Arc::new(BvhNode::from_list_with(
+                        &mut list,
+                        BvhOptions { split, ..self.bvh },
+                    )),
+                    _ => Arc::new(list),
+                }
+            }
+        };
+        Ok(object)
+    }
+}
+
+fn transform_step(step: &TransformStep) -> Result<Transform> {
+    let degenerate = |message: &str| Error::DegenerateGeometry(message.to_string());
+    Ok(match step {
+        TransformStep::Translate(offset) => Transform::translate(vec3(*offset)),
+        TransformStep::Scale(factors) => {
+            if factors.iter().any(|f| *f == 0.0 || !f.is_finite()) {
+                return Err(degenerate("scale factors must be non-zero"));
+            }
+            Transform::scale(vec3(*factors))
+        }
+        TransformStep::RotateX(angle) => Transform::rotate_x(*angle),
+        TransformStep::RotateY(angle) => Transform::rotate_y(*angle),
+        TransformStep::RotateZ(angle) => Transform::rotate_z(*angle),
+        TransformStep::Rotate { axis, angle } => {
+            if vec3(*axis).near_zero() {
+                return Err(degenerate("rotation axis must be non-zero"));
+            }
+            Transform::rotate(vec3(*axis), *angle)
+        }
+        TransformStep::Matrix([a, b, c]) => {
+            Transform::from_matrix([*a, *b, *c, [0.0, 0.0, 0.0, 1.0]])
+                .ok_or_else(|| degenerate("matrix is not invertible"))?
+        }
+    })
+}
+
+fn animated_transform(keyframes: &[KeyframeDesc]) -> Result<AnimatedTransform> {
+    let degenerate = |message: String| Error::DegenerateGeometry(message);
+    let Some(first) = keyframes.first() else {
+        return Err(degenerate(
+            "animations need at least one keyframe".to_string(),
+        ));
+    };
+    for (i, key) in keyframes.iter().enumerate() {
+        if !key.time.is_finite() {
+            return Err(degenerate(format!("keyframes[{}]: time must be finite", i)));
+        }
+        // Interpolating between opposite signs would pass through a zero scale.
+        let flat = (0..3).any(|c| {
+            key.scale[c] == 0.0
+                || !key.scale[c].is_finite()
+                || key.scale[c].signum() != first.scale[c].signum()
+        });
+        if flat {
+            return Err(degenerate(format!(
+                "keyframes[{}]: scale factors must be non-zero and keep their sign",
+                i
+            )));
+        }
+    }
+    Ok(AnimatedTransform::from(
+        keyframes
+            .iter()
+            .map(|key| Keyframe {
+                time: key.time,
+                translate: vec3(key.translate),
+                rotate: vec3(key.rotate),
+                scale: vec3(key.scale),
+            })
+            .collect(),
+    ))
+}
+
+fn check_radius(radius: f64) -> Result<()> {
+    if radius == 0.0 || !radius.is_finite() {
+        return Err(Error::DegenerateGeometry(format!(
+            "sphere radius must be non-zero, got {}",
+            radius
+        )));
+    }
+    Ok(())
+}
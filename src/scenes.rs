@@ -0,0 +1,631 @@
+use std::sync::Arc;
+
+use crate::bvh::{BvhNode, BvhOptions};
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::constant_medium::ConstantMedium;
+use crate::error::Result;
+use crate::hittable::{RotateY, Translate};
+use crate::hittable_list::HittableList;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Metal};
+use crate::quad::{box_volume, Quad};
+use crate::rtweekend::{random_double, random_double_interval};
+use crate::sphere::Sphere;
+use crate::texture::{CheckerTexture, ImageTexture, NoiseTexture};
+use crate::tlas::{Blas, Tlas};
+use crate::transform::Transform;
+use crate::vec3::{Point3, Vec3};
+
+pub fn random_sphere(bvh: BvhOptions) -> Result<(HittableList, Camera)> {
+    let mut world = HittableList::new();
+
+    let checker = Arc::new(CheckerTexture::from(
+        0.32,
+        Color::from(0.2, 0.3, 0.1),
+        Color::from(0.9, 0.9, 0.9),
+    ));
+
+    let ground_material = Arc::new(Lambertian::from_texture(checker));
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    )));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random_double();
+            let center = Point3::from(
+                a as f64 + 0.9 * random_double(),
+                0.2,
+                b as f64 + 0.9 * random_double(),
+            );
+
+            if (center - Point3::from(4.0, 0.2, 0.0)).length() > 0.9 {
+                // if choose_mat < 0.8 {
+                //     let albedo = Color::random() * Color::random();
+                //     let center2 = center + Vec3::from(0.0, random_double_interval(0.0, 0.5), 0.0);
+                //     world.add(Arc::new(Sphere::new_moving(
+                //         center,
+                //         center2,
+                //         0.2,
+                //         Arc::new(Lambertian::new(albedo)),
+                //     )));
+                // }
+                if choose_mat < 0.95 {
+                    let albedo = Color::random_interval(0.5, 1.0);
+                    let fuzz = random_double_interval(0.0, 0.5);
+                    let center2 = center + Vec3::from(0.0, random_double_interval(0.0, 0.5), 0.0);
+                    world.add(Arc::new(Sphere::new_moving(
+                        center,
+                        center2,
+                        0.2,
+                        Arc::new(Metal::try_new(albedo, fuzz)?),
+                    )));
+                } else {
+                    world.add(Arc::new(Sphere::new_stationnary(
+                        center,
+                        0.2,
+                        Arc::new(Dielectric::try_new(1.5)?),
+                    )));
+                }
+            }
+        }
+    }
+
+    let material1 = Arc::new(Dielectric::try_new(1.5)?);
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(0.0, 1.0, 0.0),
+        1.0,
+        material1,
+    )));
+
+    // let material2 = Arc::new(Lambertian::new(Color::from(0.4, 0.2, 0.1)));
+    // world.add(Arc::new(Sphere::new_stationnary(
+    //     Point3::from(-4.0, 1.0, 0.0),
+    //     1.0,
+    //     material2,
+    // )));
+
+    let material3 = Arc::new(Metal::try_new(Color::from(0.7, 0.6, 0.5), 0.0)?);
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(4.0, 1.0, 0.0),
+        1.0,
+        material3,
+    )));
+
+    world = HittableList::from(Arc::new(BvhNode::from_list_with(&mut world, bvh)));
+
+    let cam = Camera::new(
+        16.0 / 9.0,
+        400,
+        50,
+        50,
+        20.0,
+        Point3::from(13.0, 2.0, 3.0),
+        Point3::from(0.0, 0.0, 0.0),
+        Vec3::from(0.0, 1.0, 0.0),
+        0.02,
+        10.0,
+        Color::from(0.7, 0.8, 1.0),
+    );
+
+    Ok((world, cam))
+}
+
+pub fn two_sphere() -> Result<(HittableList, Camera)> {
+    let mut world = HittableList::new();
+
+    let checker = Arc::new(CheckerTexture::from(
+        0.8,
+        Color::from(0.2, 0.3, 0.1),
+        Color::from(0.9, 0.9, 0.9),
+    ));
+
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(0.0, -10.0, 0.0),
+        10.0,
+        Arc::new(Lambertian::from_texture(checker.clone())),
+    )));
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(0.0, 10.0, 0.0),
+        10.0,
+        Arc::new(Lambertian::from_texture(checker.clone())),
+    )));
+
+    let cam = Camera::default();
+    Ok((world, cam))
+}
+
+pub fn earth() -> Result<(HittableList, Camera)> {
+    let earth_texture = Arc::new(ImageTexture::from("earthmap.jpg")?);
+    let earth_surface = Arc::new(Lambertian::from_texture(earth_texture));
+    let globe = Arc::new(Sphere::new_stationnary(Point3::zeros(), 2.0, earth_surface));
+
+    let cam = Camera::new(
+        16.0 / 9.0,
+        400,
+        50,
+        50,
+        20.0,
+        Point3::from(0.0, 0.0, 12.0),
+        Point3::from(0.0, 0.0, 0.0),
+        Vec3::from(0.0, 1.0, 0.0),
+        0.00,
+        10.0,
+        Color::from(0.7, 0.8, 1.0),
+    );
+
+    Ok((HittableList::from(globe), cam))
+}
+
+pub fn two_perlin_noise() -> Result<(HittableList, Camera)> {
+    let mut world = HittableList::new();
+
+    let pertext = Arc::new(NoiseTexture::from(256, 4.0));
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::from_texture(pertext.clone())),
+    )));
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(0.0, 2.0, 0.0),
+        2.0,
+        Arc::new(Lambertian::from_texture(pertext)),
+    )));
+    let cam = Camera::new(
+        16.0 / 9.0,
+        400,
+        100,
+        50,
+        20.0,
+        Point3::from(13.0, 2.0, 3.0),
+        Point3::from(0.0, 0.0, 0.0),
+        Vec3::from(0.0, 1.0, 0.0),
+        0.00,
+        10.0,
+        Color::from(0.7, 0.8, 1.0),
+    );
+
+    Ok((world, cam))
+}
+
+pub fn quads() -> Result<(HittableList, Camera)> {
+    let mut world = HittableList::new();
+
+    //Materials
+    let left_red = Arc::new(Lambertian::from_color(Color::from(1.0, 0.2, 0.2)));
+    let back_green = Arc::new(Lambertian::from_color(Color::from(0.2, 1.0, 0.2)));
+    let right_blue = Arc::new(Lambertian::from_color(Color::from(0.2, 0.2, 1.0)));
+    let upper_orange = Arc::new(Lambertian::from_color(Color::from(1.0, 0.5, 0.0)));
+    let lower_teal = Arc::new(Lambertian::from_color(Color::from(0.2, 0.8, 0.8)));
+
+    world.add(Arc::new(Quad::from(
+        Point3::from(-3.0, -2.0, 5.0),
+        Vec3::from(0.0, 0.0, -4.0),
+        Vec3::from(0.0, 4.0, 0.0),
+        left_red,
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(-2.0, -2.0, 0.0),
+        Vec3::from(4.0, 0.0, 0.0),
+        Vec3::from(0.0, 4.0, 0.0),
+        back_green,
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(3.0, -2.0, 1.0),
+        Vec3::from(0.0, 0.0, 4.0),
+        Vec3::from(0.0, 4.0, 0.0),
+        right_blue,
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(-2.0, 3.0, 1.0),
+        Vec3::from(4.0, 0.0, 0.0),
+        Vec3::from(0.0, 0.0, 4.0),
+        upper_orange,
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(-2.0, -3.0, 5.0),
+        Vec3::from(4.0, 0.0, 0.0),
+        Vec3::from(0.0, 0.0, -4.0),
+        lower_teal,
+    )));
+
+    let cam = Camera::new(
+        1.0,
+        400,
+        1000,
+        50,
+        80.0,
+        Point3::from(0.0, 0.0, 9.0),
+        Point3::from(0.0, 0.0, 0.0),
+        Vec3::from(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+        Color::from(0.7, 0.8, 1.0),
+    );
+    Ok((world, cam))
+}
+
+pub fn simple_light() -> Result<(HittableList, Camera)> {
+    let mut world = HittableList::new();
+
+    let pertext = Arc::new(NoiseTexture::from_default(4.0));
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::from_texture(pertext.clone())),
+    )));
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(0.0, 2.0, 0.0),
+        2.0,
+        Arc::new(Lambertian::from_texture(pertext)),
+    )));
+
+    let diff_light = Arc::new(DiffuseLight::from_color(Color::from(4.0, 4.0, 4.0)));
+    world.add(Arc::new(Quad::from(
+        Point3::from(3.0, 1.0, -2.0),
+        Vec3::from(2.0, 0.0, 0.0),
+        Point3::from(0.0, 2.0, 0.0),
+        diff_light.clone(),
+    )));
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(0.0, 7.0, 0.0),
+        2.0,
+        diff_light,
+    )));
+
+    let cam = Camera::new(
+        16.0 / 9.0,
+        400,
+        100,
+        50,
+        20.0,
+        Point3::from(26.0, 3.0, 6.0),
+        Point3::from(0.0, 2.0, 0.0),
+        Vec3::from(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+        Color::zeros(),
+    );
+    Ok((world, cam))
+}
+
+pub fn cornell_box() -> Result<(HittableList, Camera)> {
+    let mut world = HittableList::new();
+    let red = Arc::new(Lambertian::from_color(Color::from(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::from_color(Color::from(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::from_color(Color::from(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::from_color(Color::from(15.0, 15.0, 15.0)));
+
+    world.add(Arc::new(Quad::from(
+        Point3::from(555.0, 0.0, 0.0),
+        Vec3::from(0.0, 555.0, 0.0),
+        Vec3::from(0.0, 0.0, 555.0),
+        green,
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(0.0, 0.0, 0.0),
+        Vec3::from(0.0, 555.0, 0.0),
+        Vec3::from(0.0, 0.0, 555.0),
+        red,
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(343.0, 554.0, 332.0),
+        Vec3::from(-130.0, 0.0, 0.0),
+        Vec3::from(0.0, 0.0, -105.0),
+        light,
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::zeros(),
+        Vec3::from(555.0, 0.0, 0.0),
+        Vec3::from(0.0, 0.0, 555.0),
+        white.clone(),
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(555.0, 555.0, 555.0),
+        Vec3::from(-555.0, 0.0, 0.0),
+        Vec3::from(0.0, 0.0, -555.0),
+        white.clone(),
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(0.0, 0.0, 555.0),
+        Vec3::from(555.0, 0.0, 0.0),
+        Vec3::from(0.0, 555.0, 0.0),
+        white.clone(),
+    )));
+
+    // world.add(Arc::new(box_volume(
+    //     Point3::from(130.0, 0.0, 65.0),
+    //     Point3::from(295.0, 165.0, 230.0),
+    //     white.clone(),
+    // )));
+    // world.add(Arc::new(box_volume(
+    //     Point3::from(265.0, 0.0, 295.0),
+    //     Point3::from(430.0, 330.0, 460.0),
+    //     white.clone(),
+    // )));
+
+    let box1 = Arc::new(box_volume(
+        Point3::zeros(),
+        Point3::from(165.0, 330.0, 165.0),
+        white.clone(),
+    ));
+    let box1 = Arc::new(RotateY::from(box1, 15.0));
+    let box1 = Arc::new(Translate::from(box1, Vec3::from(265.0, 0.0, 295.0)));
+    world.add(box1);
+
+    let box2 = Arc::new(box_volume(
+        Point3::zeros(),
+        Point3::from(165.0, 165.0, 165.0),
+        white.clone(),
+    ));
+    let box2 = Arc::new(RotateY::from(box2, -18.0));
+    let box2 = Arc::new(Translate::from(box2, Vec3::from(130.0, 0.0, 65.0)));
+    world.add(box2);
+
+    let cam = Camera::new(
+        1.0,
+        600,
+        200,
+        50,
+        40.0,
+        Point3::from(278.0, 278.0, -800.0),
+        Point3::from(278.0, 278.0, 0.0),
+        Vec3::from(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+        Color::zeros(),
+    );
+    Ok((world, cam))
+}
+
+pub fn cornell_smoke() -> Result<(HittableList, Camera)> {
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::from_color(Color::from(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::from_color(Color::from(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::from_color(Color::from(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::from_color(Color::from(7.0, 7.0, 7.0)));
+
+    world.add(Arc::new(Quad::from(
+        Point3::from(555.0, 0.0, 0.0),
+        Vec3::from(0.0, 555.0, 0.0),
+        Vec3::from(0.0, 0.0, 555.0),
+        green,
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(0.0, 0.0, 0.0),
+        Vec3::from(0.0, 555.0, 0.0),
+        Vec3::from(0.0, 0.0, 555.0),
+        red,
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(113.0, 554.0, 127.0),
+        Vec3::from(330.0, 0.0, 0.0),
+        Vec3::from(0.0, 0.0, 305.0),
+        light,
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::zeros(),
+        Vec3::from(555.0, 0.0, 0.0),
+        Vec3::from(0.0, 0.0, 555.0),
+        white.clone(),
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(555.0, 555.0, 555.0),
+        Vec3::from(-555.0, 0.0, 0.0),
+        Vec3::from(0.0, 0.0, -555.0),
+        white.clone(),
+    )));
+    world.add(Arc::new(Quad::from(
+        Point3::from(0.0, 0.0, 555.0),
+        Vec3::from(555.0, 0.0, 0.0),
+        Vec3::from(0.0, 555.0, 0.0),
+        white.clone(),
+    )));
+
+    let box1 = Arc::new(box_volume(
+        Point3::zeros(),
+        Point3::from(165.0, 330.0, 165.0),
+        white.clone(),
+    ));
+    let box1 = Arc::new(RotateY::from(box1, 15.0));
+    let box1 = Arc::new(Translate::from(box1, Vec3::from(265.0, 0.0, 295.0)));
+
+    let box2 = Arc::new(box_volume(
+        Point3::zeros(),
+        Point3::from(165.0, 165.0, 165.0),
+        white.clone(),
+    ));
+    let box2 = Arc::new(RotateY::from(box2, -18.0));
+    let box2 = Arc::new(Translate::from(box2, Vec3::from(130.0, 0.0, 65.0)));
+
+    world.add(Arc::new(ConstantMedium::from_color(
+        box1,
+        0.01,
+        Color::zeros(),
+    )));
+    world.add(Arc::new(ConstantMedium::from_color(
+        box2,
+        0.01,
+        Color::ones(),
+    )));
+    let cam = Camera::new(
+        1.0,
+        600,
+        200,
+        50,
+        40.0,
+        Point3::from(278.0, 278.0, -800.0),
+        Point3::from(278.0, 278.0, 0.0),
+        Vec3::from(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+        Color::zeros(),
+    );
+    Ok((world, cam))
+}
+
+pub fn final_scene(
+    image_width: i32,
+    samples_per_pixel: i32,
+    max_depth: i32,
+    bvh: BvhOptions,
+) -> Result<(HittableList, Camera)> {
+    // The marble lattice is drawn first, as `scenes/final_scene.json` draws it before
+    // anything else, so that the two give the same image.
+    let pertext = Arc::new(NoiseTexture::from_default(0.1));
+    let ground = Arc::new(Lambertian::from_color(Color::from(0.48, 0.83, 0.53)));
+
+    // The ground is a single unit box, scaled and moved into place by each instance.
+    let unit_box = Blas::from_list(
+        &mut box_volume(Point3::zeros(), Point3::from(1.0, 1.0, 1.0), ground),
+        bvh,
+    );
+    let mut boxes1 = Vec::new();
+
+    let boxes_per_side = 20;
+
+    for i in 0..boxes_per_side {
+        for j in 0..boxes_per_side {
+            let w = 100.0;
+            let x0 = -1000.0 + i as f64 * w;
+            let z0 = -1000.0 + j as f64 * w;
+            let y0 = 0.0;
+            let x1 = x0 + w;
+            let y1 = random_double_interval(1.0, 101.0);
+            let z1 = z0 + w;
+
+            let size = Transform::scale(Vec3::from(x1 - x0, y1 - y0, z1 - z0));
+            boxes1.push((
+                unit_box.clone(),
+                size.then(Transform::translate(Vec3::from(x0, y0, z0))),
+            ));
+        }
+    }
+
+    let mut world = HittableList::new();
+
+    world.add(Arc::new(Tlas::from(boxes1, bvh)));
+
+    let light = Arc::new(DiffuseLight::from_color(Color::from(7.0, 7.0, 7.0)));
+    world.add(Arc::new(Quad::from(
+        Point3::from(125.0, 554.0, 147.0),
+        Vec3::from(300.0, 0.0, 0.0),
+        Point3::from(0.0, 0.0, 265.0),
+        light,
+    )));
+
+    let center1 = Point3::from(400.0, 400.0, 200.0);
+    let center2 = center1 + Vec3::from(30.0, 0.0, 0.0);
+    let sphere_material = Arc::new(Lambertian::from_color(Color::from(0.7, 0.3, 0.1)));
+    world.add(Arc::new(Sphere::new_moving(
+        center1,
+        center2,
+        50.0,
+        sphere_material,
+    )));
+
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(260.0, 150.0, 45.0),
+        50.0,
+        Arc::new(Dielectric::try_new(1.5)?),
+    )));
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(0.0, 150.0, 145.0),
+        50.0,
+        Arc::new(Metal::try_new(Color::from(0.8, 0.8, 0.9), 1.0)?),
+    )));
+
+    let boundary = Arc::new(Sphere::new_stationnary(
+        Point3::from(360.0, 150.0, 145.0),
+        70.0,
+        Arc::new(Dielectric::try_new(1.5)?),
+    ));
+    world.add(boundary.clone());
+    world.add(Arc::new(ConstantMedium::from_color(
+        boundary,
+        0.2,
+        Color::from(0.2, 0.4, 0.9),
+    )));
+    let boundary = Arc::new(Sphere::new_stationnary(
+        Point3::zeros(),
+        5000.0,
+        Arc::new(Dielectric::try_new(1.5)?),
+    ));
+    world.add(Arc::new(ConstantMedium::from_color(
+        boundary,
+        0.0001,
+        Color::ones(),
+    )));
+
+    let emat = Arc::new(Lambertian::from_texture(Arc::new(ImageTexture::from(
+        "earthmap.jpg",
+    )?)));
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(400.0, 200.0, 400.0),
+        100.0,
+        emat,
+    )));
+    world.add(Arc::new(Sphere::new_stationnary(
+        Point3::from(220.0, 2880.0, 300.0),
+        80.0,
+        Arc::new(Lambertian::from_texture(pertext)),
+    )));
+
+    let mut boxes2 = HittableList::new();
+    let white = Arc::new(Lambertian::from_color(Color::from(0.73, 0.73, 0.73)));
+    let ns = 100;
+    for _ in 0..ns {
+        boxes2.add(Arc::new(Sphere::new_stationnary(
+            Point3::random_interval(0.0, 165.0),
+            10.0,
+            white.clone(),
+        )));
+    }
+
+    let r = RotateY::from(Arc::new(BvhNode::from_list_with(&mut boxes2, bvh)), 15.0);
+    let t = Translate::from(Arc::new(r), Vec3::from(-100.0, 270.0, 395.0));
+    world.add(Arc::new(t));
+
+    let cam = Camera::new(
+        1.0,
+        image_width,
+        samples_per_pixel,
+        max_depth,
+        40.0,
+        Point3::from(478.0, 278.0, -600.0),
+        Point3::from(278.0, 278.0, 0.0),
+        Vec3::from(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+        Color::zeros(),
+    );
+
+    Ok((world, cam))
+}
+
+// A scene builder, given how to build the BVHs of the scenes that have some.
+pub type Scene = fn(BvhOptions) -> Result<(HittableList, Camera)>;
+
+// Built-in demo scenes, by name.
+pub const SCENES: [(&str, Scene); 10] = [
+    ("random_sphere", random_sphere),
+    ("two_sphere", |_| two_sphere()),
+    ("earth", |_| earth()),
+    ("two_perlin_noise", |_| two_perlin_noise()),
+    ("quads", |_| quads()),
+    ("simple_light", |_| simple_light()),
+    ("cornell_box", |_| cornell_box()),
+    ("cornell_smoke", |_| cornell_smoke()),
+    ("final_scene", |bvh| final_scene(800, 10000, 40, bvh)),
+    ("final_scene_preview", |bvh| final_scene(400, 50, 4, bvh)),
+];
+
+pub fn find(name: &str) -> Option<Scene> {
+    SCENES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, scene)| *scene)
+}
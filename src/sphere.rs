@@ -1,5 +1,5 @@
 use std::f64::consts::PI;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::aabb::Aabb;
 use crate::color::Color;
@@ -13,7 +13,7 @@ use crate::vec3::{Point3, Vec3};
 pub struct Sphere {
     center: Point3,
     radius: f64,
-    mat: Rc<dyn Material>,
+    mat: Arc<dyn Material>,
     is_moving: bool,
     center_vec: Vec3,
     bbox: Aabb,
@@ -24,7 +24,7 @@ impl Sphere {
         Self {
             center: Point3::zeros(),
             radius: 0.0,
-            mat: Rc::new(Metal::new(Color::zeros(), 1.5)),
+            mat: Arc::new(Metal::new(Color::zeros(), 1.0)),
             is_moving: false,
             center_vec: Vec3::zeros(),
             bbox: Aabb::new(),
@@ -33,7 +33,7 @@ impl Sphere {
     pub fn from(
         center: Point3,
         radius: f64,
-        mat: Rc<dyn Material>,
+        mat: Arc<dyn Material>,
         center_vec: Vec3,
         is_moving: bool,
         bbox: Aabb,
@@ -47,7 +47,7 @@ impl Sphere {
             bbox,
         }
     }
-    pub fn new_stationnary(center: Point3, radius: f64, mat: Rc<dyn Material>) -> Self {
+    pub fn new_stationnary(center: Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
         let rvec = Vec3::from(radius, radius, radius);
         let bbox = Aabb::from_points(center - rvec, center + rvec);
         Self {
@@ -63,7 +63,7 @@ impl Sphere {
         center1: Point3,
         center2: Point3,
         radius: f64,
-        mat: Rc<dyn Material>,
+        mat: Arc<dyn Material>,
     ) -> Self {
         let rvec = Vec3::from(radius, radius, radius);
         let box1 = Aabb::from_points(center1 - rvec, center2 + rvec);
@@ -92,10 +92,8 @@ impl Sphere {
 
         (phi / (2.0 * PI), theta / PI)
     }
-}
-
-impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    // The nearest root within `ray_t`, and the center of the sphere at the ray's time.
+    fn intersect(&self, r: &Ray, ray_t: Interval) -> Option<(f64, Point3)> {
         let center = match self.is_moving {
             true => self.sphere_center(r.time()),
             false => self.center,
@@ -107,7 +105,7 @@ impl Hittable for Sphere {
 
         let discriminant = half_b * half_b - a * c;
         if discriminant < 0.0 {
-            return false;
+            return None;
         }
         let sqrtd = discriminant.sqrt();
 
@@ -116,9 +114,18 @@ impl Hittable for Sphere {
         if !ray_t.surrounds(root) {
             root = (-half_b + sqrtd) / a;
             if !ray_t.surrounds(root) {
-                return false;
+                return None;
             }
         }
+        Some((root, center))
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let Some((root, center)) = self.intersect(r, ray_t) else {
+            return false;
+        };
 
         rec.t = root;
         rec.p = r.at(rec.t);
@@ -127,9 +134,29 @@ impl Hittable for Sphere {
         rec.set_face_normal(r, &outward_normal);
         (rec.u, rec.v) = Sphere::get_sphere_uv(&outward_normal);
         rec.mat = self.mat.clone();
+        rec.color = None;
         true
     }
+    fn occluded(&self, r: &Ray, ray_t: Interval) -> bool {
+        self.intersect(r, ray_t).is_some()
+    }
     fn bounding_box(&self) -> Aabb {
         self.bbox
     }
+    fn refit(&mut self, time: Interval) {
+        if self.is_moving {
+            let rvec = Vec3::from(self.radius, self.radius, self.radius);
+            let [box1, box2] = [time.min(), time.max()].map(|t| {
+                let center = self.sphere_center(t);
+                Aabb::from_points(center - rvec, center + rvec)
+            });
+            self.bbox = Aabb::from_bbox(box1, box2);
+        }
+    }
+}
+
+impl Default for Sphere {
+    fn default() -> Self {
+        Self::new()
+    }
 }
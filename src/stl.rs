@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+use crate::vec3::Point3;
+
+// Read an ASCII or binary STL file. Corners at the same position are merged into one
+// vertex. Facet normals are ignored, the triangles being flat anyway.
+pub fn load<P: AsRef<Path>>(path: P, mat: Arc<dyn Material>) -> Result<TriangleMesh> {
+    let path = path.as_ref();
+    let data = fs::read(path).map_err(|source| Error::Io {
+        path: path.into(),
+        source,
+    })?;
+    let malformed = |message: String| Error::Malformed {
+        path: path.into(),
+        message,
+    };
+
+    // Binary files may also start with "solid", their size is the reliable sign.
+    let binary = data.len() >= 84 && {
+        let count = u32::from_le_bytes(data[80..84].try_into().unwrap()) as usize;
+        data.len() == 84 + count * 50
+    };
+    let triangles = match binary {
+        true => binary_triangles(&data),
+        false => ascii_triangles(&String::from_utf8_lossy(&data)).map_err(malformed)?,
+    };
+
+    let mut positions = Vec::new();
+    let mut vertices: HashMap<[u64; 3], u32> = HashMap::new();
+    let indices = triangles
+        .iter()
+        .map(|triangle| {
+            triangle.map(|p| {
+                let key = [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+                *vertices.entry(key).or_insert_with(|| {
+                    positions.push(p);
+                    positions.len() as u32 - 1
+                })
+            })
+        })
+        .collect();
+    Ok(TriangleMesh::from(positions, indices, mat))
+}
+
+// An 80 byte header and a triangle count, then per triangle a normal, three corners
+// and a two byte attribute.
+fn binary_triangles(data: &[u8]) -> Vec<[Point3; 3]> {
+    let float = |at: usize| f32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as f64;
+    let point = |at: usize| Point3::from(float(at), float(at + 4), float(at + 8));
+    (84..data.len())
+        .step_by(50)
+        .map(|at| [point(at + 12), point(at + 24), point(at + 36)])
+        .collect()
+}
+
+fn ascii_triangles(text: &str) -> std::result::Result<Vec<[Point3; 3]>, String> {
+    if !text.trim_start().starts_with("solid") {
+        return Err("not an STL file".to_string());
+    }
+    let mut triangles = Vec::new();
+    let mut corners = Vec::with_capacity(3);
+    for (n, line) in text.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("vertex") => {
+                let values = tokens
+                    .map(|t| t.parse::<f64>())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .ok()
+                    .filter(|v| v.len() == 3)
+                    .ok_or(format!("line {}: invalid vertex", n + 1))?;
+                corners.push(Point3::from(values[0], values[1], values[2]));
+            }
+            Some("endloop") => {
+                let [a, b, c] = corners[..] else {
+                    return Err(format!("line {}: facets need 3 vertices", n + 1));
+                };
+                triangles.push([a, b, c]);
+                corners.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::interval::Interval;
+    use crate::material::Lambertian;
+    use crate::ray::Ray;
+    use crate::vec3::Vec3;
+
+    fn load_bytes(name: &str, data: &[u8]) -> Result<TriangleMesh> {
+        let path =
+            std::env::temp_dir().join(format!("ray_tracer_rs-{}-{}", std::process::id(), name));
+        fs::write(&path, data).unwrap();
+        let mesh = load(&path, Arc::new(Lambertian::from_color(Color::ones())));
+        fs::remove_file(&path).unwrap();
+        mesh
+    }
+
+    // Distance to the mesh along a ray going down z from above (x, y).
+    fn depth_at(mesh: &TriangleMesh, x: f64, y: f64) -> Option<f64> {
+        let r = Ray::from(Point3::from(x, y, 1.0), Vec3::from(0.0, 0.0, -1.0), 0.0);
+        let mut rec = HitRecord::new();
+        mesh.hit(&r, Interval::from(0.001, f64::INFINITY), &mut rec)
+            .then_some(rec.t)
+    }
+
+    #[test]
+    fn binary_with_a_solid_header() {
+        let mut data = b"solid exported by a tool that writes binary files".to_vec();
+        data.resize(80, b' ');
+        data.extend(2u32.to_le_bytes());
+        // Two triangles of the unit square, sharing the corners of the diagonal.
+        for corners in [
+            [0.0, 0.0, 1.0, 0.0, 1.0, 1.0],
+            [0.0, 0.0, 1.0, 1.0, 0.0, 1.0],
+        ] {
+            data.extend([0.0f32, 0.0, 1.0].iter().flat_map(|v| v.to_le_bytes()));
+            for xy in corners.chunks(2) {
+                let corner = [xy[0], xy[1], 0.0f32];
+                data.extend(corner.iter().flat_map(|v| v.to_le_bytes()));
+            }
+            data.extend([0, 0]);
+        }
+
+        let mesh = load_bytes("solid_binary.stl", &data).unwrap();
+        assert_eq!(mesh.len(), 2);
+        assert_eq!(depth_at(&mesh, 0.75, 0.25), Some(1.0));
+        assert_eq!(depth_at(&mesh, 0.25, 0.75), Some(1.0));
+        assert_eq!(depth_at(&mesh, 1.5, 0.5), None);
+    }
+
+    #[test]
+    fn ascii() {
+        let text =
+            "solid triangle\n  facet normal 0 0 1\n    outer loop\n      vertex 0 0 0\n      \
+                    vertex 1 0 0\n      vertex 0 1 0\n    endloop\n  endfacet\nendsolid triangle\n";
+        let mesh = load_bytes("triangle.stl", text.as_bytes()).unwrap();
+        assert_eq!(mesh.len(), 1);
+        assert_eq!(depth_at(&mesh, 0.25, 0.25), Some(1.0));
+        assert_eq!(depth_at(&mesh, 0.75, 0.75), None);
+
+        let error = load_bytes("not.stl", b"facet normal 0 0 1\n").err();
+        assert!(
+            matches!(error, Some(Error::Malformed { .. })),
+            "{:?}",
+            error
+        );
+    }
+}
@@ -1,10 +1,22 @@
-use image::{io::Reader as ImageReader, DynamicImage, Pixel, Rgb};
-
-use crate::{color::Color, interval::Interval, perlin::Perlin, vec3::Point3};
-use std::{error, rc::Rc};
-
-pub trait Texture {
+use image::{io::Reader as ImageReader, ImageError, RgbImage};
+
+use crate::{
+    color::Color,
+    error::{Error, Result},
+    hittable::HitRecord,
+    interval::Interval,
+    perlin::Perlin,
+    vec3::Point3,
+};
+use std::sync::Arc;
+
+pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+    // Value at a surface hit, for textures that need more than its coordinates.
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.value(rec.u, rec.v, &rec.p)
+    }
 }
 
 pub struct SolidColor {
@@ -28,19 +40,19 @@ impl SolidColor {
 }
 
 impl Texture for SolidColor {
-    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+    fn value(&self, _: f64, _: f64, _: &Point3) -> Color {
         self.color_value
     }
 }
 
 pub struct CheckerTexture {
     inv_scale: f64,
-    even: Rc<dyn Texture>,
-    odd: Rc<dyn Texture>,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
 }
 
 impl CheckerTexture {
-    pub fn new(inv_scale: f64, even: Rc<dyn Texture>, odd: Rc<dyn Texture>) -> Self {
+    pub fn new(inv_scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
         Self {
             inv_scale: 1.0 / inv_scale,
             even,
@@ -50,8 +62,8 @@ impl CheckerTexture {
     pub fn from(scale: f64, c1: Color, c2: Color) -> Self {
         Self {
             inv_scale: 1.0 / scale,
-            even: Rc::new(SolidColor::from_color(c1)),
-            odd: Rc::new(SolidColor::from_color(c2)),
+            even: Arc::new(SolidColor::from_color(c1)),
+            odd: Arc::new(SolidColor::from_color(c2)),
         }
     }
 }
@@ -65,19 +77,37 @@ impl Texture for CheckerTexture {
         let is_even = (x_integer + y_integer + z_integer) % 2 == 0;
 
         match is_even {
-            true => self.even.value(u, v, &p),
-            false => self.odd.value(u, v, &p),
+            true => self.even.value(u, v, p),
+            false => self.odd.value(u, v, p),
         }
     }
 }
 
 pub struct ImageTexture {
-    image: DynamicImage,
+    image: RgbImage,
 }
 
 impl ImageTexture {
-    pub fn from<S: AsRef<str>>(image_path: S) -> Result<Self, Box<dyn error::Error>> {
-        let image = ImageReader::open(image_path.as_ref())?.decode()?;
+    pub fn from<S: AsRef<str>>(image_path: S) -> Result<Self> {
+        let path = image_path.as_ref();
+        let image = ImageReader::open(path)
+            .map_err(ImageError::IoError)
+            .and_then(|reader| reader.decode())
+            .map_err(|source| Error::Image {
+                path: path.into(),
+                source,
+            })?;
+        if image.width() == 0 || image.height() == 0 {
+            return Err(Error::InvalidTexture(format!("{} is empty", path)));
+        }
+        Ok(Self {
+            image: image.to_rgb8(),
+        })
+    }
+    pub fn from_image(image: RgbImage) -> Result<Self> {
+        if image.width() == 0 || image.height() == 0 {
+            return Err(Error::InvalidTexture("empty image".to_string()));
+        }
         Ok(Self { image })
     }
 
@@ -94,24 +124,16 @@ impl ImageTexture {
 
 impl Texture for ImageTexture {
     fn value(&self, u: f64, v: f64, _: &Point3) -> Color {
-        if self.image.height() <= 0 {
-            return Color::from(0.0, 1.0, 1.0);
-        }
         let u = Interval::from(0.0, 1.0).clamp(u);
         let v = 1.0 - Interval::from(0.0, 1.0).clamp(v);
 
         let i = (u * self.image.width() as f64) as u32;
         let j = (v * self.image.height() as f64) as u32;
 
-        let bindings = self.image.to_rgb8();
-        let pixel = bindings
-            .get_pixel_checked(
-                // i,
-                // j,
-                ImageTexture::clamp(i, 0, self.image.width()),
-                ImageTexture::clamp(j, 0, self.image.height()),
-            )
-            .unwrap_or(Rgb::from_slice(&[255, 0, 255]));
+        let pixel = self.image.get_pixel(
+            ImageTexture::clamp(i, 0, self.image.width()),
+            ImageTexture::clamp(j, 0, self.image.height()),
+        );
 
         let color_scale = 1.0 / 255.0;
         Color::from(
@@ -122,6 +144,44 @@ impl Texture for ImageTexture {
     }
 }
 
+// Colors of the mesh vertices, interpolated over each triangle, and `fallback` on
+// surfaces without vertex colors. With a `base` texture, vertex colors tint it
+// instead, as they do glTF base colors.
+pub struct VertexColor {
+    fallback: Color,
+    base: Option<Arc<dyn Texture>>,
+}
+
+impl VertexColor {
+    pub fn from(fallback: Color) -> Self {
+        Self {
+            fallback,
+            base: None,
+        }
+    }
+    pub fn tinting(base: Arc<dyn Texture>) -> Self {
+        Self {
+            fallback: Color::ones(),
+            base: Some(base),
+        }
+    }
+}
+
+impl Texture for VertexColor {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        match &self.base {
+            Some(base) => base.value(u, v, p),
+            None => self.fallback,
+        }
+    }
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        match &self.base {
+            Some(base) => base.value_at(rec) * rec.color.unwrap_or(Color::ones()),
+            None => rec.color.unwrap_or(self.fallback),
+        }
+    }
+}
+
 pub struct NoiseTexture {
     noise: Perlin,
     scale: f64,
@@ -163,3 +223,9 @@ impl Texture for NoiseTexture {
         Color::ones() * 0.5 * (1.0 + (s.z() + 10.0 * self.noise.turb(s, 7)).sin())
     }
 }
+
+impl Default for SolidColor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::{BvhNode, BvhOptions};
+use crate::hittable::{refit_object, HitRecord, Hittable, Instance};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::transform::Transform;
+
+// Bottom-level structure: geometry put under a BVH once, in its own space, for any
+// number of instances to share. Clones share the same tree.
+#[derive(Clone)]
+pub struct Blas {
+    geometry: Arc<dyn Hittable>,
+}
+
+impl Blas {
+    pub fn from_list(list: &mut HittableList, options: BvhOptions) -> Self {
+        Self {
+            geometry: Arc::new(BvhNode::from_list_with(list, options)),
+        }
+    }
+    // Geometry that already has a structure of its own, such as a triangle mesh.
+    pub fn from(geometry: Arc<dyn Hittable>) -> Self {
+        Self { geometry }
+    }
+    pub fn geometry(&self) -> &Arc<dyn Hittable> {
+        &self.geometry
+    }
+
+    // The geometry placed by `transform`, as an entry of a `Tlas`. Instances only
+    // hold their transform and a reference to the tree.
+    pub fn instance(&self, transform: Transform) -> Arc<dyn Hittable> {
+        match transform.is_identity() {
+            true => self.geometry.clone(),
+            false => Arc::new(Instance::from(self.geometry.clone(), transform)),
+        }
+    }
+    pub fn bounding_box(&self) -> Aabb {
+        self.geometry.bounding_box()
+    }
+}
+
+// Top-level structure: a BVH over the boxes of instances of bottom-level ones, so
+// that memory grows with the unique geometry rather than with the instance count.
+// Each instance is a `Blas` placed by a transform. Hits report the index + 1 of
+// their instance as object id.
+pub struct Tlas {
+    bvh: BvhNode,
+    // Every bottom-level structure once, and the structure and transform of each
+    // instance, to place them again after a refit.
+    blases: Vec<Blas>,
+    placements: Vec<(usize, Transform)>,
+    options: BvhOptions,
+}
+
+impl Tlas {
+    pub fn from(instances: Vec<(Blas, Transform)>, options: BvhOptions) -> Self {
+        let mut blases = Vec::new();
+        let mut indices = HashMap::new();
+        let mut placements = Vec::with_capacity(instances.len());
+        for (blas, transform) in instances {
+            let key = Arc::as_ptr(&blas.geometry) as *const () as usize;
+            let index = *indices.entry(key).or_insert_with(|| {
+                blases.push(blas);
+                blases.len() - 1
+            });
+            placements.push((index, transform));
+        }
+        let mut tlas = Self {
+            bvh: BvhNode::new(),
+            blases,
+            placements,
+            options,
+        };
+        tlas.place();
+        tlas
+    }
+    pub fn len(&self) -> usize {
+        self.placements.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.placements.is_empty()
+    }
+
+    fn place(&mut self) {
+        let mut list = HittableList::new();
+        for (index, transform) in &self.placements {
+            list.add(self.blases[*index].instance(*transform));
+        }
+        self.bvh = BvhNode::from_list_with(&mut list, self.options);
+    }
+}
+
+impl Hittable for Tlas {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        self.bvh.hit(r, ray_t, rec)
+    }
+    fn occluded(&self, r: &Ray, ray_t: Interval) -> bool {
+        self.bvh.occluded(r, ray_t)
+    }
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+    // The instances are dropped so that the geometry they share is held here alone,
+    // refit once per `Blas`, and placed again under a new top-level tree. Geometry
+    // whose `Blas` is still held elsewhere cannot be refit and keeps its boxes.
+    fn refit(&mut self, time: Interval) {
+        self.bvh = BvhNode::new();
+        for blas in &mut self.blases {
+            refit_object(&mut blas.geometry, time);
+        }
+        self.place();
+    }
+}
@@ -0,0 +1,333 @@
+use std::ops;
+
+use crate::aabb::Aabb;
+use crate::interval::{Interval, UNIVERSE};
+use crate::rtweekend::{degrees2radians, INF};
+use crate::vec3::{Point3, Vec3};
+
+// Row-major 4x4 matrix whose last row is always (0, 0, 0, 1).
+pub type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+// An invertible affine transform, stored with its inverse. `a * b` applies `b` first,
+// and `a.then(b)` applies `a` first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            matrix: IDENTITY,
+            inverse: IDENTITY,
+        }
+    }
+    pub fn translate(offset: Vec3) -> Self {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for c in 0..3 {
+            matrix[c][3] = offset[c];
+            inverse[c][3] = -offset[c];
+        }
+        Self { matrix, inverse }
+    }
+    // Scale factors must be non-zero for the transform to be invertible.
+    pub fn scale(factors: Vec3) -> Self {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for c in 0..3 {
+            matrix[c][c] = factors[c];
+            inverse[c][c] = 1.0 / factors[c];
+        }
+        Self { matrix, inverse }
+    }
+    // Counterclockwise rotation in degrees about `axis`, looking down the axis.
+    pub fn rotate(axis: Vec3, angle: f64) -> Self {
+        let a = axis.unit_vector();
+        let radians = degrees2radians(angle);
+        let (sin, cos) = radians.sin_cos();
+        let k = 1.0 - cos;
+        let matrix = [
+            [
+                cos + a.x() * a.x() * k,
+                a.x() * a.y() * k - a.z() * sin,
+                a.x() * a.z() * k + a.y() * sin,
+                0.0,
+            ],
+            [
+                a.y() * a.x() * k + a.z() * sin,
+                cos + a.y() * a.y() * k,
+                a.y() * a.z() * k - a.x() * sin,
+                0.0,
+            ],
+            [
+                a.z() * a.x() * k - a.y() * sin,
+                a.z() * a.y() * k + a.x() * sin,
+                cos + a.z() * a.z() * k,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        Self {
+            matrix,
+            inverse: transpose(&matrix),
+        }
+    }
+    pub fn rotate_x(angle: f64) -> Self {
+        Self::rotate(Vec3::X(false), angle)
+    }
+    pub fn rotate_y(angle: f64) -> Self {
+        Self::rotate(Vec3::Y(false), angle)
+    }
+    pub fn rotate_z(angle: f64) -> Self {
+        Self::rotate(Vec3::Z(false), angle)
+    }
+    // Any affine matrix, `None` when it cannot be inverted or is not affine.
+    pub fn from_matrix(matrix: Matrix) -> Option<Self> {
+        if matrix[3] != [0.0, 0.0, 0.0, 1.0] {
+            return None;
+        }
+        let column = |c: usize| Vec3::from(matrix[0][c], matrix[1][c], matrix[2][c]);
+        let (a, b, c) = (column(0), column(1), column(2));
+        let det = a.dot(b.cross(c));
+        if det.abs() < 1e-12 || !det.is_finite() {
+            return None;
+        }
+
+        // The rows of the inverse of a 3x3 matrix are the cross products of its columns.
+        let rows = [b.cross(c) / det, c.cross(a) / det, a.cross(b) / det];
+        let translation = column(3);
+        let mut inverse = IDENTITY;
+        for (r, row) in rows.iter().enumerate() {
+            inverse[r] = [row.x(), row.y(), row.z(), -row.dot(translation)];
+        }
+        Some(Self { matrix, inverse })
+    }
+
+    pub fn then(self, next: Transform) -> Self {
+        next * self
+    }
+    pub fn inverse(self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+    pub fn matrix(&self) -> &Matrix {
+        &self.matrix
+    }
+    pub fn is_identity(&self) -> bool {
+        self.matrix == IDENTITY
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        apply(&self.matrix, p, 1.0)
+    }
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        apply(&self.matrix, v, 0.0)
+    }
+    // Normals go through the inverse transpose so they stay perpendicular to the
+    // surface under non-uniform scale. The result is not normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        apply(&transpose(&self.inverse), n, 0.0)
+    }
+    pub fn inverse_point(&self, p: Point3) -> Point3 {
+        apply(&self.inverse, p, 1.0)
+    }
+    pub fn inverse_vector(&self, v: Vec3) -> Vec3 {
+        apply(&self.inverse, v, 0.0)
+    }
+
+    // Box holding the eight transformed corners of `bbox`.
+    pub fn bbox(&self, bbox: Aabb) -> Aabb {
+        bounds(&corners(bbox).map(|p| self.point(p)), 0.0)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl ops::Mul for Transform {
+    type Output = Transform;
+    fn mul(self, rhs: Self) -> Self::Output {
+        Transform {
+            matrix: multiply(&self.matrix, &rhs.matrix),
+            inverse: multiply(&rhs.inverse, &self.inverse),
+        }
+    }
+}
+
+// A pose of an animated object: scaled, then rotated about x, y and z in turn by the
+// angles in degrees, then translated. Poses in between keyframes interpolate each of
+// these linearly, so a rotation from 0 to 720 degrees spins the object twice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translate: Vec3,
+    pub rotate: Vec3,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    // The object as modelled, at `time`.
+    pub fn at(time: f64) -> Self {
+        Self {
+            time,
+            translate: Vec3::zeros(),
+            rotate: Vec3::zeros(),
+            scale: Vec3::from(1.0, 1.0, 1.0),
+        }
+    }
+    pub fn transform(&self) -> Transform {
+        Transform::scale(self.scale)
+            .then(Transform::rotate_x(self.rotate.x()))
+            .then(Transform::rotate_y(self.rotate.y()))
+            .then(Transform::rotate_z(self.rotate.z()))
+            .then(Transform::translate(self.translate))
+    }
+    fn lerp(&self, next: &Keyframe, time: f64) -> Keyframe {
+        let s = match next.time > self.time {
+            true => (time - self.time) / (next.time - self.time),
+            false => 1.0,
+        };
+        let mix = |a: Vec3, b: Vec3| a * (1.0 - s) + b * s;
+        Keyframe {
+            time,
+            translate: mix(self.translate, next.translate),
+            rotate: mix(self.rotate, next.rotate),
+            scale: mix(self.scale, next.scale),
+        }
+    }
+}
+
+// Keyframes over the shutter interval, in ray time. Before the first and after the
+// last keyframe the object holds still. Scale factors must not be zero, nor change
+// sign between keyframes, for every pose to be invertible.
+#[derive(Debug, Clone)]
+pub struct AnimatedTransform {
+    keyframes: Vec<Keyframe>,
+}
+
+impl AnimatedTransform {
+    pub fn from(mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(!keyframes.is_empty(), "animations need a keyframe");
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { keyframes }
+    }
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        let k = &self.keyframes;
+        match k.partition_point(|key| key.time <= time) {
+            0 => k[0].transform(),
+            n if n == k.len() => k[n - 1].transform(),
+            n => k[n - 1].lerp(&k[n], time).transform(),
+        }
+    }
+
+    // Box holding `bbox` all along the motion.
+    pub fn bbox(&self, bbox: Aabb) -> Aabb {
+        self.bbox_during(bbox, UNIVERSE)
+    }
+    // Box holding `bbox` while `time` lasts. Each stretch between keyframes within it
+    // is sampled, and the box grown by half the longest step a corner takes between
+    // two samples, which is more than a rotating corner swings out from the straight
+    // step.
+    pub fn bbox_during(&self, bbox: Aabb, time: Interval) -> Aabb {
+        const STEPS: usize = 32;
+        let k = &self.keyframes;
+        let start = time.min().max(k[0].time);
+        let end = time.max().min(k[k.len() - 1].time);
+        if start >= end {
+            return self.at(start).bbox(bbox);
+        }
+        let mut times = vec![start];
+        times.extend(
+            k.iter()
+                .map(|key| key.time)
+                .filter(|&t| start < t && t < end),
+        );
+        times.push(end);
+
+        let corners = corners(bbox);
+        let mut points = Vec::new();
+        let mut step: f64 = 0.0;
+        for pair in times.windows(2) {
+            let start = self.at(pair[0]);
+            let mut previous = corners.map(|p| start.point(p));
+            for i in 1..=STEPS {
+                let time = pair[0] + (pair[1] - pair[0]) * i as f64 / STEPS as f64;
+                let pose = self.at(time);
+                let current = corners.map(|p| pose.point(p));
+                for (a, b) in previous.iter().zip(&current) {
+                    step = step.max((*b - *a).length());
+                }
+                points.extend(previous);
+                previous = current;
+            }
+            points.extend(previous);
+        }
+        bounds(&points, step / 2.0)
+    }
+}
+
+fn corners(bbox: Aabb) -> [Point3; 8] {
+    std::array::from_fn(|i| {
+        Point3::from(
+            [bbox.x().min(), bbox.x().max()][i & 1],
+            [bbox.y().min(), bbox.y().max()][(i >> 1) & 1],
+            [bbox.z().min(), bbox.z().max()][(i >> 2) & 1],
+        )
+    })
+}
+
+fn bounds(points: &[Point3], margin: f64) -> Aabb {
+    let mut min = Point3::from(INF, INF, INF);
+    let mut max = Point3::from(-INF, -INF, -INF);
+    for p in points {
+        for c in 0..3 {
+            min[c] = f64::min(min[c], p[c] - margin);
+            max[c] = f64::max(max[c], p[c] + margin);
+        }
+    }
+    Aabb::from_points(min, max).pad()
+}
+
+fn apply(m: &Matrix, v: Vec3, w: f64) -> Vec3 {
+    let row = |r: usize| m[r][0] * v.x() + m[r][1] * v.y() + m[r][2] * v.z() + m[r][3] * w;
+    Vec3::from(row(0), row(1), row(2))
+}
+
+// Only the 3x3 part is transposed, which is all `normal` needs.
+fn transpose(m: &Matrix) -> Matrix {
+    let mut t = IDENTITY;
+    for (r, row) in t.iter_mut().enumerate().take(3) {
+        for (c, value) in row.iter_mut().enumerate().take(3) {
+            *value = m[c][r];
+        }
+    }
+    t
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.0; 4]; 4];
+    for (r, row) in m.iter_mut().enumerate() {
+        for (c, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[r][k] * b[k][c]).sum();
+        }
+    }
+    m
+}
@@ -0,0 +1,122 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::Ray,
+    vec3::{Point3, Vec3},
+};
+use std::sync::Arc;
+
+pub struct Triangle {
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>, // Per-vertex shading normals, the face normal otherwise
+    uvs: [(f64, f64); 3],
+    mat: Arc<dyn Material>,
+    bbox: Aabb,
+    normal: Vec3,
+}
+
+impl Triangle {
+    pub fn from(a: Point3, b: Point3, c: Point3, mat: Arc<dyn Material>) -> Self {
+        Self {
+            vertices: [a, b, c],
+            normals: None,
+            uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            mat,
+            bbox: Self::set_bounding_box(a, b, c),
+            normal: (b - a).cross(c - a).unit_vector(),
+        }
+    }
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals.map(Vec3::unit_vector));
+        self
+    }
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = uvs;
+        self
+    }
+    pub fn set_bounding_box(a: Point3, b: Point3, c: Point3) -> Aabb {
+        Aabb::from_bbox(Aabb::from_points(a, b), Aabb::from_points(a, c)).pad()
+    }
+}
+
+// Möller-Trumbore intersection, returning the ray parameter and the barycentric
+// weights of the second and third vertices.
+pub(crate) fn intersect(
+    r: &Ray,
+    ray_t: Interval,
+    [a, b, c]: [Point3; 3],
+) -> Option<(f64, f64, f64)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let pvec = r.direction().cross(edge2);
+    let det = edge1.dot(pvec);
+    // No hit if the ray is parallel to the plane, scaled so that tiny triangles still hit.
+    if det.abs() <= 1e-12 * edge1.length() * edge2.length() * r.direction().length() {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin() - a;
+    let beta = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&beta) {
+        return None;
+    }
+    let qvec = tvec.cross(edge1);
+    let gamma = r.direction().dot(qvec) * inv_det;
+    if gamma < 0.0 || beta + gamma > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(qvec) * inv_det;
+    if !ray_t.contains(t) {
+        return None;
+    }
+    Some((t, beta, gamma))
+}
+
+// Fill the hit record of a triangle hit from its barycentric weights, interpolating
+// the texture coordinates and, when given, the shading normals.
+pub(crate) fn set_hit_record(
+    r: &Ray,
+    rec: &mut HitRecord,
+    (t, beta, gamma): (f64, f64, f64),
+    normal: Vec3,
+    normals: Option<[Vec3; 3]>,
+    uvs: [(f64, f64); 3],
+    mat: &Arc<dyn Material>,
+) {
+    let alpha = 1.0 - beta - gamma;
+    rec.t = t;
+    rec.p = r.at(t);
+    rec.u = alpha * uvs[0].0 + beta * uvs[1].0 + gamma * uvs[2].0;
+    rec.v = alpha * uvs[0].1 + beta * uvs[1].1 + gamma * uvs[2].1;
+    rec.mat = mat.clone();
+    rec.color = None;
+    // The face normal decides which side was hit, the shading normal is then
+    // turned to that same side.
+    rec.set_face_normal(r, &normal);
+    if let Some([na, nb, nc]) = normals {
+        let shading = (na * alpha + nb * beta + nc * gamma).unit_vector();
+        rec.normal = match shading.dot(rec.normal) < 0.0 {
+            true => shading * -1.0,
+            false => shading,
+        };
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let Some(hit) = intersect(r, ray_t, self.vertices) else {
+            return false;
+        };
+        set_hit_record(r, rec, hit, self.normal, self.normals, self.uvs, &self.mat);
+        true
+    }
+    fn occluded(&self, r: &Ray, ray_t: Interval) -> bool {
+        intersect(r, ray_t, self.vertices).is_some()
+    }
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
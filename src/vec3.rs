@@ -1,7 +1,7 @@
 use crate::rtweekend::{random_double, random_double_interval};
 use std::{fmt, ops};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct Vec3 {
     x: f64,
     y: f64,
@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use ray_tracer_rs::interval::Interval;
+use ray_tracer_rs::rtweekend::{random_double, random_double_interval, seed_random, DEFAULT_SEED};
+use ray_tracer_rs::{
+    box_volume, Blas, BvhNode, BvhOptions, BvhSplit, BvhWidth, Color, HitRecord, Hittable,
+    HittableList, Instance, Lambertian, Material, Point3, Quad, Ray, RotateY, Sphere, Tlas,
+    Transform, Translate, Triangle, TriangleMesh, Vec3,
+};
+
+const RAYS: usize = 2000;
+
+// Spheres, some of them moving, quads, triangles, a mesh and transformed boxes in
+// a cube 20 units wide. Constant mediums are left out, their hits being random.
+fn scene() -> HittableList {
+    let mat: Arc<dyn Material> = Arc::new(Lambertian::from_color(Color::from(0.5, 0.5, 0.5)));
+    let mut world = HittableList::new();
+    for i in 0..60 {
+        let center = Point3::random_interval(-10.0, 10.0);
+        let radius = random_double_interval(0.3, 1.2);
+        world.add(Arc::new(match i % 2 {
+            0 => Sphere::new_stationnary(center, radius, mat.clone()),
+            _ => Sphere::new_moving(
+                center,
+                center + Vec3::random_interval(-2.0, 2.0),
+                radius,
+                mat.clone(),
+            ),
+        }));
+    }
+    for _ in 0..20 {
+        world.add(Arc::new(Quad::from(
+            Point3::random_interval(-10.0, 10.0),
+            Vec3::random_interval(-3.0, 3.0),
+            Vec3::random_interval(-3.0, 3.0),
+            mat.clone(),
+        )));
+        let a = Point3::random_interval(-10.0, 10.0);
+        world.add(Arc::new(Triangle::from(
+            a,
+            a + Vec3::random_interval(-3.0, 3.0),
+            a + Vec3::random_interval(-3.0, 3.0),
+            mat.clone(),
+        )));
+    }
+
+    // A bumpy 8 by 8 grid.
+    let n = 8;
+    let positions = (0..=n)
+        .flat_map(|i| (0..=n).map(move |j| (i, j)))
+        .map(|(i, j)| Point3::from(i as f64 - 4.0, random_double(), j as f64 - 4.0))
+        .collect();
+    let indices = (0..n)
+        .flat_map(|i| (0..n).map(move |j| i * (n + 1) + j))
+        .flat_map(|v| [[v, v + 1, v + n + 1], [v + 1, v + n + 2, v + n + 1]])
+        .collect();
+    world.add(Arc::new(TriangleMesh::from(
+        positions,
+        indices,
+        mat.clone(),
+    )));
+
+    let unit_box = || -> Arc<dyn Hittable> {
+        Arc::new(box_volume(
+            Point3::zeros(),
+            Point3::from(1.0, 2.0, 3.0),
+            mat.clone(),
+        ))
+    };
+    let transform = Transform::rotate(Vec3::from(1.0, 1.0, 0.0), 30.0)
+        .then(Transform::scale(Vec3::from(2.0, 1.0, 1.0)))
+        .then(Transform::translate(Vec3::from(3.0, -2.0, 1.0)));
+    world.add(Arc::new(Instance::from(unit_box(), transform)));
+    world.add(Arc::new(Translate::from(
+        Arc::new(RotateY::from(unit_box(), 40.0)),
+        Vec3::from(-5.0, 4.0, -3.0),
+    )));
+    world
+}
+
+// Rays from anywhere around the scene, at times across `time`, ending at a random
+// distance so that occlusion queries get cut short.
+fn rays(time: Interval) -> Vec<(Ray, Interval)> {
+    (0..RAYS)
+        .map(|_| {
+            let origin = Point3::random_interval(-12.0, 12.0);
+            let time = random_double_interval(time.min(), time.max());
+            let r = Ray::from(origin, Vec3::random_unit_vector(), time);
+            (r, Interval::from(0.001, random_double_interval(1.0, 40.0)))
+        })
+        .collect()
+}
+
+fn closest(world: &dyn Hittable, r: &Ray, ray_t: Interval) -> Option<(f64, u32)> {
+    let mut rec = HitRecord::new();
+    world
+        .hit(r, ray_t, &mut rec)
+        .then_some((rec.t, rec.object_id))
+}
+
+#[test]
+fn bvh_agrees_with_the_list() {
+    let mut list = scene();
+    let rays = rays(Interval::from(0.0, 1.0));
+    let expected: Vec<_> = rays.iter().map(|(r, t)| closest(&list, r, *t)).collect();
+    assert!(expected.iter().filter(|hit| hit.is_some()).count() > RAYS / 10);
+    for ((r, t), hit) in rays.iter().zip(&expected) {
+        assert_eq!(list.occluded(r, *t), hit.is_some());
+    }
+
+    for split in BvhSplit::ALL {
+        for width in BvhWidth::ALL {
+            let bvh = BvhNode::build(&mut list, split, width);
+            let name = format!("{} split, width {}", split.name(), width.name());
+            for ((r, t), hit) in rays.iter().zip(&expected) {
+                assert_eq!(closest(&bvh, r, *t), *hit, "{}", name);
+                assert_eq!(bvh.occluded(r, *t), hit.is_some(), "{}", name);
+            }
+        }
+    }
+}
+
+// A tree refit to a shutter window, first within the motion it was built for and
+// then past it, finds what a tree built for that window does.
+#[test]
+fn refit_agrees_with_a_fresh_build() {
+    for split in BvhSplit::ALL {
+        for width in BvhWidth::ALL {
+            seed_random(DEFAULT_SEED);
+            let mut refit = BvhNode::build(&mut scene(), split, width);
+            let name = format!("{} split, width {}", split.name(), width.name());
+            for time in [Interval::from(0.25, 0.5), Interval::from(1.5, 2.0)] {
+                refit.refit(time);
+                seed_random(DEFAULT_SEED);
+                let mut list = scene();
+                list.refit(time);
+                let fresh = BvhNode::build(&mut list, split, width);
+                for (r, t) in rays(time) {
+                    assert_eq!(closest(&refit, &r, t), closest(&fresh, &r, t), "{}", name);
+                    assert_eq!(refit.occluded(&r, t), fresh.occluded(&r, t), "{}", name);
+                }
+            }
+        }
+    }
+}
+
+// A moving sphere shared by two instances goes on moving past time 1, out of the
+// box it was built with, and the top-level structure follows it there.
+#[test]
+fn shared_geometry_is_refit_past_its_motion() {
+    let mat: Arc<dyn Material> = Arc::new(Lambertian::from_color(Color::from(0.5, 0.5, 0.5)));
+    let mut list = HittableList::new();
+    list.add(Arc::new(Sphere::new_moving(
+        Point3::zeros(),
+        Point3::from(1.0, 0.0, 0.0),
+        0.5,
+        mat,
+    )));
+    let blas = Blas::from_list(&mut list, BvhOptions::default());
+    drop(list);
+    let offsets = [Vec3::zeros(), Vec3::from(0.0, 0.0, 5.0)];
+    let mut tlas = Tlas::from(
+        offsets
+            .iter()
+            .map(|offset| (blas.clone(), Transform::translate(*offset)))
+            .collect(),
+        BvhOptions::default(),
+    );
+    drop(blas);
+
+    let time = 2.0;
+    tlas.refit(Interval::from(time, time));
+    for (i, offset) in offsets.iter().enumerate() {
+        let above = Point3::from(2.0, 5.0, 0.0) + *offset;
+        let r = Ray::from(above, Vec3::from(0.0, -1.0, 0.0), time);
+        let hit = closest(&tlas, &r, Interval::from(0.001, 10.0));
+        assert_eq!(hit, Some((4.5, i as u32 + 1)));
+    }
+}
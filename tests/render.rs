@@ -0,0 +1,40 @@
+use ray_tracer_rs::aov::Aov;
+use ray_tracer_rs::rtweekend::{seed_random, DEFAULT_SEED};
+use ray_tracer_rs::{scenes, BvhOptions, Framebuffer};
+
+// A small render of `random_sphere`, which has moving spheres, defocus blur and a
+// BVH, with every pass recorded. The scene is built from the default seed each
+// time, rendering from `seed`.
+fn render(threads: usize, seed: u64) -> Framebuffer {
+    seed_random(DEFAULT_SEED);
+    let (world, mut cam) = scenes::random_sphere(BvhOptions::default()).unwrap();
+    cam.image_width = 32;
+    cam.samples_per_pixel = 4;
+    cam.max_depth = 8;
+    cam.threads = threads;
+    cam.seed = seed;
+    cam.aovs = Aov::ALL.to_vec();
+    cam.render(&world)
+}
+
+fn assert_same(a: &Framebuffer, b: &Framebuffer) {
+    assert_eq!(a.to_rgb8(), b.to_rgb8());
+    assert_eq!(a.pixels(), b.pixels());
+    for ((aov, pass), (_, other)) in a.aovs().iter().zip(b.aovs()) {
+        assert_eq!(pass.pixels(), other.pixels(), "{} pass", aov.name());
+    }
+}
+
+#[test]
+fn thread_count_leaves_the_image_unchanged() {
+    let single = render(1, 7);
+    for threads in [2, 4] {
+        assert_same(&single, &render(threads, 7));
+    }
+}
+
+#[test]
+fn seed_picks_the_samples() {
+    assert_same(&render(1, 7), &render(1, 7));
+    assert_ne!(render(1, 7).pixels(), render(1, 8).pixels());
+}